@@ -1,12 +1,120 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::eyre;
 use color_eyre::Report;
 use colored::*;
 use structopt::StructOpt;
 
+use advent2021_lib::day02;
+use advent2021_lib::day05;
+use advent2021_lib::day09;
+use advent2021_lib::day10;
+use advent2021_lib::day11;
+use advent2021_lib::day13;
+use advent2021_lib::day15;
+use advent2021_lib::day17;
+use advent2021_lib::day18;
+use advent2021_lib::day19;
 use advent2021_lib::get_days;
 use advent2021_lib::get_input;
 
+#[derive(StructOpt)]
+enum Command {
+    /// Step through the reduction of the sum of two snailfish numbers
+    Snailfish {
+        left: String,
+        right: String,
+        /// Wait for enter between steps
+        #[structopt(long)]
+        step: bool,
+    },
+    /// Run day 2 submarine commands, printing where each one leaves the submarine
+    Dive {
+        /// Commands to use instead of the puzzle input
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+        /// Use the second part's dialect, where up and down change the aim
+        #[structopt(long)]
+        aim: bool,
+        /// Write the dive profile as SVG
+        #[structopt(long, parse(from_os_str))]
+        svg: Option<PathBuf>,
+    },
+    /// Draw the day 5 vents as a heat map of overlapping lines, as .png, .ppm or .svg
+    Vents {
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+        /// Lines to use instead of the puzzle input
+        #[structopt(long, parse(from_os_str))]
+        file: Option<PathBuf>,
+        /// Pixels per position
+        #[structopt(long, default_value = "1")]
+        scale: usize,
+        /// Leave out the diagonal lines
+        #[structopt(long)]
+        axis_only: bool,
+    },
+    /// Fold the day 13 transparent paper one instruction at a time
+    Origami {
+        /// Instructions to use instead of the puzzle input
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+        /// Wait for enter between folds
+        #[structopt(long)]
+        step: bool,
+    },
+    /// Plot day 17 probe launches, the highest arc unless a velocity is given
+    Probe {
+        /// Target area to use instead of the puzzle input, e.g. "x=20..30, y=-10..-5"
+        #[structopt(long)]
+        target: Option<String>,
+        /// Launch velocity as x,y
+        #[structopt(long, allow_hyphen_values = true)]
+        velocity: Option<String>,
+        /// Write the plot as SVG
+        #[structopt(long, parse(from_os_str))]
+        svg: Option<PathBuf>,
+        /// Include every velocity that hits the target in the SVG
+        #[structopt(long)]
+        all: bool,
+    },
+    /// Check that the delimiters in each line of a file nest properly
+    Brackets {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Delimiter pairs as consecutive open and close characters, e.g. "()[]"
+        #[structopt(long)]
+        pairs: Option<String>,
+        /// Ignore characters that aren't delimiters
+        #[structopt(long)]
+        skip_unknown: bool,
+    },
+    /// Export the day 19 beacon map, as .json, .ply or .obj
+    BeaconMap {
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Print the day 9 heightmap coloured by basin
+    Basins,
+    /// Animate the day 11 octopuses in the terminal until they synchronise
+    Octopus {
+        /// Write the frames as an animated GIF instead of animating them
+        #[structopt(long, parse(from_os_str))]
+        gif: Option<PathBuf>,
+    },
+    /// Print the day 15 lowest risk path over the expanded map
+    Path {
+        /// Write the path as SVG instead of printing it
+        #[structopt(long, parse(from_os_str))]
+        svg: Option<PathBuf>,
+    },
+}
+
 #[derive(StructOpt)]
 struct Cli {
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
     puzzle: Option<usize>,
 
     #[structopt(long)]
@@ -42,6 +150,35 @@ fn main() -> Result<(), Report> {
     let args = Cli::from_args();
     let days = get_days();
 
+    if let Some(command) = args.command {
+        return match command {
+            Command::Snailfish { left, right, step } => snailfish(&left, &right, step),
+            Command::Dive { file, aim, svg } => dive(file.as_deref(), aim, svg.as_deref()),
+            Command::Vents {
+                output,
+                file,
+                scale,
+                axis_only,
+            } => vents(&output, file.as_deref(), scale, !axis_only),
+            Command::Origami { file, step } => origami(file.as_deref(), step),
+            Command::Probe {
+                target,
+                velocity,
+                svg,
+                all,
+            } => probe(target.as_deref(), velocity.as_deref(), svg.as_deref(), all),
+            Command::Brackets {
+                file,
+                pairs,
+                skip_unknown,
+            } => brackets(&file, pairs.as_deref(), skip_unknown),
+            Command::BeaconMap { output } => export_map(&output),
+            Command::Basins => basins(),
+            Command::Octopus { gif } => octopus(gif.as_deref()),
+            Command::Path { svg } => show_path(svg.as_deref()),
+        };
+    }
+
     if args.all {
         for (day_num, day) in days.into_iter() {
             let (part1, part2) = day.both(&get_input(day_num)).expect("invalid input");
@@ -80,6 +217,355 @@ fn main() -> Result<(), Report> {
     Ok(())
 }
 
+fn export_map(path: &std::path::Path) -> Result<(), Report> {
+    let report = day19::parse(&get_input(19)).map_err(|e| eyre!("{}", e))?;
+    let map = report.map().map_err(|e| eyre!("{}", e))?;
+    let contents = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => map.to_json(),
+        Some("ply") => map.to_ply(),
+        Some("obj") => map.to_obj(),
+        _ => return Err(eyre!("unknown export format: {}", path.display())),
+    };
+    std::fs::write(path, contents)?;
+    println!("Exported beacon map to {}", path.display());
+    Ok(())
+}
+
+fn basins() -> Result<(), Report> {
+    const COLOURS: [Color; 6] = [
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+    ];
+    let heightmap = day09::parse(&get_input(9)).map_err(|e| eyre!("{}", e))?;
+    for (y, row) in heightmap.basin_labels().into_iter().enumerate() {
+        for (x, label) in row.into_iter().enumerate() {
+            let height = heightmap.get(&(x, y)).unwrap_or(9).to_string();
+            match label {
+                Some(label) => print!("{}", height.color(COLOURS[label % COLOURS.len()])),
+                None => print!("{}", height.dimmed()),
+            }
+        }
+        println!();
+    }
+    println!();
+    let mut basins = heightmap.basins();
+    basins.sort_by_key(|basin| std::cmp::Reverse(basin.size()));
+    println!("{} basins, the largest:", basins.len());
+    for basin in basins.iter().take(3) {
+        println!(
+            "  {} cells around the low point at {:?}",
+            basin.size(),
+            basin.low_point
+        );
+    }
+    Ok(())
+}
+
+fn octopus(gif: Option<&std::path::Path>) -> Result<(), Report> {
+    const MAX_STEPS: usize = 10_000;
+    let energy_levels = day11::parse(&get_input(11)).map_err(|e| eyre!("{}", e))?;
+    let cycle = energy_levels
+        .cycle(MAX_STEPS)
+        .ok_or_else(|| eyre!("no cycle within {} steps", MAX_STEPS))?;
+    // Show the cycle going round once
+    let frames: Vec<day11::Frame> = energy_levels
+        .simulate()
+        .take(cycle.start + cycle.period)
+        .collect();
+    match gif {
+        Some(gif) => {
+            std::fs::write(gif, day11::to_gif(&frames, 8, 4))?;
+            println!("Wrote {} frames to {}", frames.len(), gif.display());
+        }
+        None => {
+            print!("\x1b[2J");
+            for frame in day11::ansi_frames(&frames) {
+                print!("{}", frame);
+                std::thread::sleep(std::time::Duration::from_millis(40));
+            }
+        }
+    }
+    match cycle.first_sync {
+        Some(step) => println!("All octopuses first flash together on step {}", step),
+        None => println!("The octopuses never all flash together"),
+    }
+    println!(
+        "From step {} the octopuses repeat every {} steps",
+        cycle.start, cycle.period
+    );
+    Ok(())
+}
+
+fn show_path(svg: Option<&std::path::Path>) -> Result<(), Report> {
+    let map = day15::parse(&get_input(15))
+        .map_err(|e| eyre!("{}", e))?
+        .expanded();
+    let (path, risk) = day15::lowest_risk_path(&map);
+    match svg {
+        Some(svg) => {
+            std::fs::write(svg, map.render_svg(&path))?;
+            println!("Wrote path to {}", svg.display());
+        }
+        None => {
+            for line in map.render_ascii(&path).lines() {
+                println!("{}", line.replace('.', &".".dimmed().to_string()));
+            }
+            println!();
+        }
+    }
+    println!("Total risk: {}", risk);
+    Ok(())
+}
+
+fn snailfish(left: &str, right: &str, step: bool) -> Result<(), Report> {
+    let left = day18::SnailfishNumber::parse_reduced(left).map_err(|e| eyre!("{}: {}", e, left))?;
+    let right =
+        day18::SnailfishNumber::parse_reduced(right).map_err(|e| eyre!("{}: {}", e, right))?;
+    println!("  {}", left);
+    println!("+ {}", right);
+    let (combined, steps) = left.add_steps(right);
+    println!("= {}", combined);
+    let mut sum = combined;
+    for (idx, reduction_step) in steps.enumerate() {
+        if step {
+            std::io::stdin().read_line(&mut String::new())?;
+        }
+        println!(
+            "{:>3}: {}",
+            idx + 1,
+            reduction_step.reduction.to_string().yellow()
+        );
+        println!("     {}", reduction_step.after);
+        sum = reduction_step.after;
+    }
+    println!();
+    println!("Sum: {}", sum.to_string().bold());
+    println!("Magnitude: {}", sum.magnitude());
+    Ok(())
+}
+
+fn dive(
+    file: Option<&std::path::Path>,
+    aim: bool,
+    svg: Option<&std::path::Path>,
+) -> Result<(), Report> {
+    // Longer runs are only shown in the SVG
+    const MAX_LINES: usize = 40;
+    let input = match file {
+        Some(file) => std::fs::read_to_string(file)?,
+        None => get_input(2),
+    };
+    let commands = day02::get_data(&input).map_err(|e| eyre!("{}", e))?;
+    let dialect = if aim {
+        day02::Dialect::aim()
+    } else {
+        day02::Dialect::simple()
+    };
+    let history = dialect.run(&commands).map_err(|e| eyre!("{}", e))?;
+    if commands.0.len() <= MAX_LINES {
+        for (command, state) in commands.0.iter().zip(&history[1..]) {
+            println!(
+                "{:<12} position {:>6}, depth {:>8}, aim {:>5}",
+                format!(
+                    "{} {}",
+                    command.name,
+                    command
+                        .args
+                        .iter()
+                        .map(|arg| arg.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )
+                .yellow(),
+                state.position,
+                state.depth,
+                state.aim
+            );
+        }
+    }
+    let end = history.last().unwrap();
+    println!(
+        "After {} commands the submarine is at position {}, depth {}",
+        commands.0.len(),
+        end.position,
+        end.depth
+    );
+    if let Some(svg) = svg {
+        std::fs::write(svg, day02::render_svg(&history))?;
+        println!("Wrote the dive profile to {}", svg.display());
+    }
+    Ok(())
+}
+
+fn vents(
+    output: &std::path::Path,
+    file: Option<&std::path::Path>,
+    scale: usize,
+    diag: bool,
+) -> Result<(), Report> {
+    let input = match file {
+        Some(file) => std::fs::read_to_string(file)?,
+        None => get_input(5),
+    };
+    let lines = day05::parse(&input).map_err(|e| eyre!("{}", e))?;
+    let vents = day05::plot(&lines, diag);
+    let (width, height) = vents.dimensions();
+    if width == 0 || scale == 0 {
+        return Err(eyre!("nothing to draw"));
+    }
+    let contents = match output.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => vents.to_png(scale),
+        Some("ppm") => vents.to_ppm(scale),
+        Some("svg") => vents.render_svg(scale).into_bytes(),
+        _ => return Err(eyre!("unknown image format: {}", output.display())),
+    };
+    std::fs::write(output, contents)?;
+    println!("Drew {}x{} vents to {}", width, height, output.display());
+    let max = vents.max_overlap();
+    for overlaps in 1..=max {
+        let [r, g, b] = day05::heat_colour(overlaps, max);
+        let positions = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|pos| vents.get(pos) == overlaps)
+            .count();
+        println!(
+            "{} {} lines: {} positions",
+            "██".truecolor(r, g, b),
+            overlaps,
+            positions
+        );
+    }
+    Ok(())
+}
+
+fn origami(file: Option<&std::path::Path>, step: bool) -> Result<(), Report> {
+    // Wider sheets would wrap in the terminal
+    const MAX_WIDTH: usize = 120;
+    let input = match file {
+        Some(file) => std::fs::read_to_string(file)?,
+        None => get_input(13),
+    };
+    // Not `day13::parse`, the folds that work are still shown before one that doesn't
+    let instructions: day13::TransparentPaper = input.parse().map_err(|e| eyre!("{}", e))?;
+    let paper = instructions.paper();
+    let (width, height) = paper.dimensions();
+    println!("{}x{} with {} dots", width, height, paper.dots().len());
+    for (fold, folded) in instructions.folds().iter().zip(instructions.fold_steps()) {
+        if step {
+            std::io::stdin().read_line(&mut String::new())?;
+        }
+        let folded = folded.map_err(|e| eyre!("{}", e))?;
+        let (width, height) = folded.dimensions();
+        println!(
+            "{}: {}x{} with {} dots",
+            fold.to_string().yellow(),
+            width,
+            height,
+            folded.dots().len()
+        );
+        if width <= MAX_WIDTH {
+            print!("{}", folded.render_ascii().replace('.', " "));
+        }
+    }
+    Ok(())
+}
+
+fn probe(
+    target: Option<&str>,
+    velocity: Option<&str>,
+    svg: Option<&std::path::Path>,
+    all: bool,
+) -> Result<(), Report> {
+    // Bigger plots don't fit in a terminal
+    const MAX_ASCII: usize = 120;
+    let input = match target {
+        Some(target) => format!("target area: {}", target),
+        None => get_input(17),
+    };
+    // Not `day17::parse`, a given velocity can still be plotted for an unbounded target
+    let target_area: day17::TargetArea = input.trim().parse().map_err(|e| eyre!("{:?}", e))?;
+    let velocity = match velocity {
+        Some(velocity) => {
+            let (x, y) = velocity
+                .split_once(',')
+                .ok_or_else(|| eyre!("velocity should be x,y: {}", velocity))?;
+            day17::Velocity(x.trim().parse()?, y.trim().parse()?)
+        }
+        None => target_area.highest_velocity().map_err(|e| eyre!("{}", e))?,
+    };
+    let trajectory = target_area.trajectory(velocity);
+    let (min_y, max_y) = (target_area.min_y().min(0), trajectory.apex());
+    let (min_x, max_x) = trajectory.positions.iter().fold(
+        (target_area.min_x().min(0), target_area.max_x().max(0)),
+        |(min, max), pos| (min.min(pos.0), max.max(pos.0)),
+    );
+    if ((max_x - min_x) as usize) < MAX_ASCII && ((max_y - min_y) as usize) < MAX_ASCII {
+        print!("{}", target_area.render_ascii(&trajectory));
+        println!();
+    }
+    let outcome = if trajectory.hit {
+        "hits the target".green()
+    } else {
+        "misses the target".red()
+    };
+    println!(
+        "Launching at {},{} {} after {} steps, reaching a height of {}",
+        velocity.0,
+        velocity.1,
+        outcome,
+        trajectory.positions.len() - 1,
+        trajectory.apex()
+    );
+    if let Some(svg) = svg {
+        let velocities = if all {
+            target_area.get_velocities().map_err(|e| eyre!("{}", e))?
+        } else {
+            vec![]
+        };
+        std::fs::write(svg, target_area.render_svg(&[trajectory], &velocities))?;
+        println!("Wrote plot to {}", svg.display());
+    }
+    Ok(())
+}
+
+fn brackets(file: &std::path::Path, pairs: Option<&str>, skip_unknown: bool) -> Result<(), Report> {
+    let language = match pairs {
+        None => day10::Language::default(),
+        Some(pairs) => {
+            let chars: Vec<char> = pairs.chars().collect();
+            if chars.len() % 2 != 0 {
+                return Err(eyre!("unpaired delimiter in {:?}", pairs));
+            }
+            let delimiters = chars
+                .chunks(2)
+                .enumerate()
+                .map(|(idx, pair)| day10::Delimiter::new(pair[0], pair[1], idx + 1, idx + 1))
+                .collect();
+            day10::Language::new(delimiters).map_err(|e| eyre!("{}", e))?
+        }
+    };
+    let contents = std::fs::read_to_string(file)?;
+    let mut errors = 0;
+    for (line_num, line) in contents.lines().enumerate() {
+        let status = language.check_with(line, skip_unknown);
+        let message = match status {
+            day10::LineStatus::Valid => continue,
+            day10::LineStatus::Incomplete { .. } => status.to_string().yellow(),
+            _ => status.to_string().red(),
+        };
+        errors += 1;
+        println!("{}:{}: {}", file.display(), line_num + 1, message);
+    }
+    if errors == 0 {
+        println!("{}", "All delimiters match".green());
+    }
+    Ok(())
+}
+
 fn setup() -> Result<(), Report> {
     if std::env::var("RUST_BACKTRACE").is_err() {
         std::env::set_var("RUST_BACKTRACE", "1")
@@ -1,5 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
+use num_bigint::BigUint;
+
 use advent2021_lib::day06;
 use advent2021_lib::get_input;
 
@@ -11,7 +13,21 @@ pub fn benchmark(c: &mut Criterion) {
     });
 
     c.bench_function("fish_count_ndarray", |b| {
-        b.iter(|| day06::fish_count_array(black_box(&state.0), black_box(256)))
+        b.iter(|| day06::fish_count_ndarray(black_box(&state.0), black_box(256)))
+    });
+
+    c.bench_function("population_matrix", |b| {
+        b.iter(|| {
+            day06::PopulationModel::LANTERNFISH
+                .population::<usize>(black_box(&state.0), black_box(256))
+        })
+    });
+
+    c.bench_function("population_biguint_1m", |b| {
+        b.iter(|| {
+            day06::PopulationModel::LANTERNFISH
+                .population::<BigUint>(black_box(&state.0), black_box(1_000_000))
+        })
     });
 }
 
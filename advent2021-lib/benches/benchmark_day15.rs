@@ -0,0 +1,21 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use advent2021_lib::day15;
+use advent2021_lib::get_input;
+
+pub fn benchmark(c: &mut Criterion) {
+    let map = day15::parse(&get_input(15)).unwrap().expanded();
+    let mut group = c.benchmark_group("day15::lowest_risk");
+    group.sample_size(10);
+    for algorithm in day15::Algorithm::ALL.iter() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{:?}", algorithm)),
+            algorithm,
+            |b, &algorithm| b.iter(|| day15::lowest_risk(&map, algorithm)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark);
+criterion_main!(benches);
@@ -1,178 +1,326 @@
-use std::ops::Add;
+use std::collections::HashSet;
 use std::str::FromStr;
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
 #[derive(Debug, Clone)]
-struct DirectionError;
+pub struct CommandError;
 
-enum Direction {
-    Forward,
-    Down,
-    Up,
+/// A command name and its whole number arguments, such as `forward 5`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub name: String,
+    pub args: Vec<i64>,
 }
 
-impl FromStr for Direction {
+impl FromStr for Command {
     type Err = CommandError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "forward" => Self::Forward,
-            "down" => Self::Down,
-            "up" => Self::Up,
-            _ => return Err(CommandError {}),
+        let mut tokens = s.split_whitespace();
+        Ok(Command {
+            name: tokens.next().ok_or(CommandError)?.to_owned(),
+            args: tokens
+                .map(|token| token.parse().map_err(|_| CommandError {}))
+                .collect::<Result<_, _>>()?,
         })
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct CommandError;
+/// Where the submarine is, and where it's pointing
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct State {
+    pub position: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
 
-pub struct Command {
-    direction: Direction,
-    distance: usize,
+/// A command in a dialect, updating the state from the command's arguments
+#[derive(Clone, Copy)]
+pub struct Rule {
+    pub name: &'static str,
+    pub arity: usize,
+    pub update: fn(&mut State, &[i64]),
 }
 
-impl FromStr for Command {
-    type Err = CommandError;
+impl Rule {
+    pub const fn new(name: &'static str, arity: usize, update: fn(&mut State, &[i64])) -> Self {
+        Self {
+            name,
+            arity,
+            update,
+        }
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut tokens = s.split(' ');
-        Ok(Command {
-            direction: tokens.next().ok_or(CommandError)?.parse()?,
-            distance: tokens
-                .next()
-                .ok_or(CommandError)?
-                .parse()
-                .map_err(|_| CommandError {})?,
-        })
+impl std::fmt::Debug for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.name, self.arity)
     }
 }
 
-#[derive(Default)]
-pub struct Location {
-    position: usize,
-    depth: usize,
-}
-
-impl Add<&Command> for Location {
-    type Output = Self;
-
-    fn add(self, command: &Command) -> Self {
-        match command {
-            Command {
-                direction: Direction::Forward,
-                distance,
-            } => Self {
-                position: self.position + distance,
-                depth: self.depth,
-            },
-            Command {
-                direction: Direction::Down,
-                distance,
-            } => Self {
-                position: self.position,
-                depth: self.depth + distance,
-            },
-            Command {
-                direction: Direction::Up,
-                distance,
-            } => Self {
-                position: self.position,
-                depth: self.depth - distance,
-            },
+/// Jump straight to a position and depth
+pub const GOTO: Rule = Rule::new("goto", 2, |state, args| {
+    state.position = args[0];
+    state.depth = args[1];
+});
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DialectError {
+    /// More than one rule has the same name
+    Duplicate(&'static str),
+}
+
+impl std::fmt::Display for DialectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplicate(name) => write!(f, "{:?} is defined more than once", name),
         }
     }
 }
 
-#[derive(Default)]
-pub struct LocationAim {
-    position: usize,
-    depth: usize,
-    aim: usize,
-}
-
-impl Add<&Command> for LocationAim {
-    type Output = Self;
-
-    fn add(self, command: &Command) -> Self {
-        match command {
-            Command {
-                direction: Direction::Forward,
-                distance,
-            } => Self {
-                position: self.position + distance,
-                depth: self.depth + self.aim * distance,
-                aim: self.aim,
-            },
-            Command {
-                direction: Direction::Down,
-                distance,
-            } => Self {
-                position: self.position,
-                depth: self.depth,
-                aim: self.aim + distance,
-            },
-            Command {
-                direction: Direction::Up,
-                distance,
-            } => Self {
-                position: self.position,
-                depth: self.depth,
-                aim: self.aim - distance,
-            },
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunError {
+    /// The dialect has no rule for the command
+    Unknown { line: usize, name: String },
+    /// The command has the wrong number of arguments for its rule
+    Arity {
+        line: usize,
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown { line, name } => write!(f, "line {}: unknown command {:?}", line, name),
+            Self::Arity {
+                line,
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: {:?} takes {} arguments, but was given {}",
+                line, name, expected, found
+            ),
         }
     }
 }
 
-pub struct Commands(Vec<Command>);
+/// A set of commands and how each one moves the submarine
+#[derive(Clone, Debug)]
+pub struct Dialect {
+    rules: Vec<Rule>,
+}
+
+impl Dialect {
+    pub fn new(rules: Vec<Rule>) -> Result<Self, DialectError> {
+        let mut names = HashSet::new();
+        for rule in &rules {
+            if !names.insert(rule.name) {
+                return Err(DialectError::Duplicate(rule.name));
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// The first part's commands, moving directly, with `back` and `goto`
+    pub fn simple() -> Self {
+        Self::new(vec![
+            Rule::new("forward", 1, |state, args| state.position += args[0]),
+            Rule::new("back", 1, |state, args| state.position -= args[0]),
+            Rule::new("down", 1, |state, args| state.depth += args[0]),
+            Rule::new("up", 1, |state, args| state.depth -= args[0]),
+            GOTO,
+        ])
+        .unwrap()
+    }
+
+    /// The second part's commands, where `down` and `up` change the aim,
+    /// with `back` undoing `forward` and `goto`
+    pub fn aim() -> Self {
+        Self::new(vec![
+            Rule::new("forward", 1, |state, args| {
+                state.position += args[0];
+                state.depth += state.aim * args[0];
+            }),
+            Rule::new("back", 1, |state, args| {
+                state.position -= args[0];
+                state.depth -= state.aim * args[0];
+            }),
+            Rule::new("down", 1, |state, args| state.aim += args[0]),
+            Rule::new("up", 1, |state, args| state.aim -= args[0]),
+            GOTO,
+        ])
+        .unwrap()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Adds a rule, or replaces the one with the same name
+    pub fn with(mut self, rule: Rule) -> Self {
+        match self.rules.iter_mut().find(|other| other.name == rule.name) {
+            Some(other) => *other = rule,
+            None => self.rules.push(rule),
+        }
+        self
+    }
+
+    /// The rule running the command on the given line
+    fn rule(&self, line: usize, command: &Command) -> Result<&Rule, RunError> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.name == command.name)
+            .ok_or_else(|| RunError::Unknown {
+                line,
+                name: command.name.clone(),
+            })?;
+        if rule.arity != command.args.len() {
+            return Err(RunError::Arity {
+                line,
+                name: command.name.clone(),
+                expected: rule.arity,
+                found: command.args.len(),
+            });
+        }
+        Ok(rule)
+    }
+
+    /// Check every command has a rule taking its arguments, without running them
+    pub fn check(&self, commands: &Commands) -> Result<(), RunError> {
+        for (idx, command) in commands.0.iter().enumerate() {
+            self.rule(idx + 1, command)?;
+        }
+        Ok(())
+    }
+
+    /// Every state from the start to the end of the commands
+    pub fn run(&self, commands: &Commands) -> Result<Vec<State>, RunError> {
+        let mut state = State::default();
+        let mut history = vec![state];
+        for (idx, command) in commands.0.iter().enumerate() {
+            let rule = self.rule(idx + 1, command)?;
+            (rule.update)(&mut state, &command.args);
+            history.push(state);
+        }
+        Ok(history)
+    }
+}
+
+/// The dive profile, depth against horizontal position, stretched to fit
+pub fn render_svg(history: &[State]) -> String {
+    const WIDTH: i64 = 800;
+    const HEIGHT: i64 = 400;
+    const MARGIN: i64 = 10;
+    let (min_x, max_x, min_y, max_y) =
+        history
+            .iter()
+            .fold((0, 0, 0, 0), |(min_x, max_x, min_y, max_y), state| {
+                (
+                    min_x.min(state.position),
+                    max_x.max(state.position),
+                    min_y.min(state.depth),
+                    max_y.max(state.depth),
+                )
+            });
+    let scale = |val: i64, min: i64, max: i64, size: i64| {
+        MARGIN
+            + ((val - min) as i128 * (size - 2 * MARGIN) as i128 / (max - min).max(1) as i128)
+                as i64
+    };
+    let points: Vec<String> = history
+        .iter()
+        .map(|state| {
+            format!(
+                "{},{}",
+                scale(state.position, min_x, max_x, WIDTH),
+                scale(state.depth, min_y, max_y, HEIGHT)
+            )
+        })
+        .collect();
+    let surface = scale(0, min_y, max_y, HEIGHT);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"#0b2545\"/>\n\
+         <line x1=\"0\" y1=\"{s}\" x2=\"{w}\" y2=\"{s}\" stroke=\"#8da9c4\" stroke-dasharray=\"4\"/>\n\
+         <polyline points=\"{p}\" fill=\"none\" stroke=\"#f4d35e\" stroke-width=\"2\"/>\n\
+         <title>{n} commands, from 0,0 to {x},{y}</title>\n\
+         </svg>\n",
+        w = WIDTH,
+        h = HEIGHT,
+        s = surface,
+        p = points.join(" "),
+        n = history.len() - 1,
+        x = history.last().map_or(0, |state| state.position),
+        y = history.last().map_or(0, |state| state.depth),
+    )
+}
+
+pub struct Commands(pub Vec<Command>);
 
 pub fn get_data(input: &str) -> ParseResult<Commands> {
     Ok(Commands(
         input
             .lines()
-            .map(|line| line.parse().map_err(|_| ParseError::Empty))
+            .map(|line| line.parse().map_err(|_| ParseError::Str(line.to_owned())))
             .collect::<ParseResult<_>>()?,
     ))
 }
 
-pub fn navigate(commands: &Commands) -> Location {
-    commands
-        .0
-        .iter()
-        .fold(Location::default(), |location, command| location + command)
+/// Commands that both of the puzzle's dialects can run
+pub fn parse(input: &str) -> ParseResult<Commands> {
+    let commands = get_data(input)?;
+    for dialect in [Dialect::simple(), Dialect::aim()] {
+        dialect
+            .check(&commands)
+            .map_err(|e| ParseError::Str(e.to_string()))?;
+    }
+    Ok(commands)
 }
 
-pub fn navigate_aim(commands: &Commands) -> LocationAim {
-    commands
-        .0
-        .iter()
-        .fold(LocationAim::default(), |location, command| {
-            location + command
-        })
+fn final_state(dialect: &Dialect, commands: &Commands) -> State {
+    *dialect
+        .run(commands)
+        .expect("parsing checks the commands against both dialects")
+        .last()
+        .unwrap()
+}
+
+pub fn navigate(commands: &Commands) -> State {
+    final_state(&Dialect::simple(), commands)
+}
+
+pub fn navigate_aim(commands: &Commands) -> State {
+    final_state(&Dialect::aim(), commands)
 }
 
-pub fn part1(commands: &Commands) -> PartOutput<usize> {
+pub fn part1(commands: &Commands) -> PartOutput<i64> {
     let location = navigate(commands);
     PartOutput {
         answer: location.position * location.depth,
     }
 }
-pub fn part2(commands: &Commands) -> PartOutput<usize> {
+pub fn part2(commands: &Commands) -> PartOutput<i64> {
     let location = navigate_aim(commands);
     PartOutput {
         answer: location.position * location.depth,
     }
 }
 
-pub const DAY: Day<Commands, usize> = Day {
+pub const DAY: Day<Commands, i64> = Day {
     title: "Dive!",
     display: (
         "The horizontal position to final depth product is {answer}",
         "The horizontal position to final depth product is {answer}",
     ),
     calc: DayCalc {
-        parse: get_data,
+        parse,
         part1,
         part2,
     },
@@ -196,9 +344,103 @@ mod tests {
         assert_eq!(result.position * result.depth, 900);
     }
 
+    #[test]
+    fn test_example_history() {
+        let history = Dialect::aim().run(&get_data(DAY.example).unwrap()).unwrap();
+        assert_eq!(history.len(), 7);
+        assert_eq!(
+            history[3],
+            State {
+                position: 13,
+                depth: 40,
+                aim: 5
+            }
+        );
+        let svg = render_svg(&history);
+        let points = svg
+            .split("points=\"")
+            .nth(1)
+            .unwrap()
+            .split('"')
+            .next()
+            .unwrap();
+        assert_eq!(points.split(' ').count(), history.len());
+        assert!(points.starts_with("10,10 "));
+        assert!(svg.contains("<title>6 commands, from 0,0 to 15,60</title>"));
+    }
+
+    #[test]
+    fn test_extra_commands() {
+        let commands = get_data("forward 5\ndown 2\nback 3\ngoto 10 -4\nforward 1").unwrap();
+        assert_eq!(
+            navigate(&commands),
+            State {
+                position: 11,
+                depth: -4,
+                aim: 0
+            }
+        );
+        assert_eq!(
+            navigate_aim(&commands),
+            State {
+                position: 11,
+                depth: -2,
+                aim: 2
+            }
+        );
+        // A dialect of its own, with a dive that changes the aim and moves
+        let dialect = Dialect::simple().with(Rule::new("dive", 2, |state, args| {
+            state.aim += args[0];
+            state.position += args[1];
+            state.depth += state.aim * args[1];
+        }));
+        let history = dialect
+            .run(&get_data("dive 1 3\nup 1\ndive -1 2").unwrap())
+            .unwrap();
+        assert_eq!(
+            history.last(),
+            Some(&State {
+                position: 5,
+                depth: 2,
+                aim: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let commands = get_data("forward 5\nsideways 2").unwrap();
+        assert_eq!(
+            Dialect::simple().run(&commands).unwrap_err().to_string(),
+            "line 2: unknown command \"sideways\""
+        );
+        assert_eq!(
+            Dialect::aim().run(&get_data("goto 1").unwrap()),
+            Err(RunError::Arity {
+                line: 1,
+                name: "goto".to_string(),
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Dialect::new(vec![GOTO, GOTO]).unwrap_err(),
+            DialectError::Duplicate("goto")
+        );
+        assert!(get_data("forward five").is_err());
+        // Any command can be read, but the puzzle only takes the dialects' ones
+        assert!(get_data("forward 5\nsideways 2").is_ok());
+        assert!(matches!(
+            parse("forward 5\nsideways 2"),
+            Err(ParseError::Str(_))
+        ));
+        assert!(parse("goto 1").is_err());
+        assert!(parse(DAY.example).is_ok());
+    }
+
     #[test]
     fn test_main() {
-        let input = get_data(&get_input(2)).unwrap();
+        let input = parse(&get_input(2)).unwrap();
         assert_eq!(part1(&input).answer.to_string(), "2027977");
         assert_eq!(part2(&input).answer.to_string(), "1903644897");
     }
@@ -1,93 +1,148 @@
-use std::collections::HashSet;
-use std::fmt;
+use bitvec::prelude::*;
+use num_bigint::BigUint;
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
-pub struct Numbers(pub Vec<usize>);
+/// A line of the report, most significant bit first
+pub type Bits = BitVec<usize, Msb0>;
 
-impl fmt::Binary for Numbers {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // extract the value using tuple idexing
-        // and create reference to 'vec'
-        let vec = &self.0;
+pub struct Report {
+    pub numbers: Vec<Bits>,
+    pub width: usize,
+}
 
-        // @count -> the index of the value,
-        // @n     -> the value
-        for (count, n) in vec.iter().enumerate() {
-            if count != 0 {
-                write!(f, " ")?;
+pub fn get_report(input: &str) -> ParseResult<Report> {
+    let mut width = None;
+    let numbers = input
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            if line.is_empty() || *width.get_or_insert(line.len()) != line.len() {
+                return Err(ParseError::Str(line.to_owned()));
             }
+            line.chars()
+                .map(|c| match c {
+                    '0' => Ok(false),
+                    '1' => Ok(true),
+                    _ => Err(ParseError::Str(line.to_owned())),
+                })
+                .collect()
+        })
+        .collect::<ParseResult<Vec<Bits>>>()?;
+    Ok(Report {
+        numbers,
+        width: width.ok_or(ParseError::Empty)?,
+    })
+}
 
-            write!(f, "{:b}", n)?;
-        }
+/// The value of the bits, however many there are
+pub fn to_biguint(bits: &BitSlice<usize, Msb0>) -> BigUint {
+    bits.iter().fold(BigUint::default(), |acc, bit| {
+        (acc << 1u8) | BigUint::from(*bit as u8)
+    })
+}
 
-        Ok(())
+/// The value of the bits, `None` if there are too many to fit in a `usize`
+pub fn to_usize(bits: &BitSlice<usize, Msb0>) -> Option<usize> {
+    match bits.len() {
+        0 => Some(0),
+        len if len <= usize::BITS as usize => Some(bits.load_be()),
+        _ => None,
     }
 }
 
-pub struct Report {
-    pub numbers: Numbers,
-    pub width: usize,
+/// How the bit at one position was chosen from the lines still being considered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BitChoice {
+    /// Counting from the most significant bit
+    pub position: usize,
+    pub zeros: usize,
+    pub ones: usize,
+    pub bit: bool,
 }
 
-pub fn get_report(input: &str) -> ParseResult<Report> {
-    Ok(Report {
-        numbers: Numbers(
-            input
-                .lines()
-                .map(|line| usize::from_str_radix(line, 2).map_err(ParseError::Int))
-                .collect::<ParseResult<_>>()?,
-        ),
-        width: input.lines().next().unwrap().len(),
-    })
+impl BitChoice {
+    /// Whether the bit was decided by the tie break rather than the counts
+    pub fn is_tie(&self) -> bool {
+        self.zeros == self.ones
+    }
+}
+
+impl std::fmt::Display for BitChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "bit {}: {} zeros, {} ones, chose {}{}",
+            self.position,
+            self.zeros,
+            self.ones,
+            u8::from(self.bit),
+            if self.is_tie() { " on a tie" } else { "" }
+        )
+    }
+}
+
+/// A value built one bit at a time, with the choice made for each bit
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub bits: Bits,
+    pub choices: Vec<BitChoice>,
+}
+
+impl Analysis {
+    pub fn value(&self) -> BigUint {
+        to_biguint(&self.bits)
+    }
+
+    pub fn ties(&self) -> impl Iterator<Item = &BitChoice> {
+        self.choices.iter().filter(|choice| choice.is_tie())
+    }
+}
+
+/// The most common bit in each column, 1 on a tie
+pub fn gamma_rate(report: &Report) -> Analysis {
+    let mut ones = vec![0; report.width];
+    for number in &report.numbers {
+        for position in number.iter_ones() {
+            ones[position] += 1;
+        }
+    }
+    let choices: Vec<BitChoice> = ones
+        .into_iter()
+        .enumerate()
+        .map(|(position, ones)| {
+            let zeros = report.numbers.len() - ones;
+            BitChoice {
+                position,
+                zeros,
+                ones,
+                bit: ones >= zeros,
+            }
+        })
+        .collect();
+    Analysis {
+        bits: choices.iter().map(|choice| choice.bit).collect(),
+        choices,
+    }
+}
+
+/// The least common bit in each column, 0 on a tie
+pub fn epsilon_rate(report: &Report) -> Bits {
+    !gamma_rate(report).bits
 }
 
 /// # Get average bitwise
 ///
+/// The most common bits as a number, `None` for reports wider than a `usize`
+///
 /// ```
-/// let report = advent2021_lib::day03::Report {
-///     numbers: advent2021_lib::day03::Numbers(vec![
-///         0b1111_1100,
-///         0b1111_0000,
-///         0b1111_0000,
-///         0b1100_0000,
-///     ]),
-///     width: 8,
-/// };
+/// let report = advent2021_lib::day03::get_report("11111100\n11110000\n11110000\n11000000").unwrap();
 /// let avg = advent2021_lib::day03::get_bitwise_avg(&report);
-/// assert_eq!(avg, 0b1111_0000);
+/// assert_eq!(avg, Some(0b1111_0000));
 /// ```
 ///
-pub fn get_bitwise_avg(report: &Report) -> usize {
-    let numbers = &report.numbers.0;
-    let width = report.width;
-    let low_upper_bound_inclusive = numbers
-        .len()
-        .checked_sub(1)
-        .unwrap()
-        .checked_div(2)
-        .unwrap();
-    let high_lower_bound_inclusive = numbers
-        .len()
-        .checked_add(2)
-        .unwrap()
-        .checked_div(2)
-        .unwrap();
-    (0..width)
-        .map(|mask_len| {
-            let row_sum = numbers
-                .iter()
-                .fold(0, |acc, val| acc + ((val >> mask_len) & 1));
-            match row_sum {
-                s if s >= high_lower_bound_inclusive => 1,
-                s if s <= low_upper_bound_inclusive => 0,
-                _ => panic!("unexpected sum"),
-            }
-        })
-        .fold((0, 0), |(shift, acc), val| {
-            (shift + 1, acc + (val << shift))
-        })
-        .1
+pub fn get_bitwise_avg(report: &Report) -> Option<usize> {
+    to_usize(&gamma_rate(report).bits)
 }
 
 ///
@@ -117,97 +172,136 @@ pub fn gamma_rate_to_power_rate(gamma_rate: usize, len: usize) -> usize {
     gamma_rate * gamma_rate_to_epsilon_rate(gamma_rate, len)
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum LifeSupport {
+    /// Keep the most common bit, 1 on a tie
     Oxygen,
+    /// Keep the least common bit, 0 on a tie
     Co2,
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+struct Node {
+    /// Lines passing through the node
+    count: usize,
+    children: [Option<usize>; 2],
+}
+
+/// The report's lines in a binary trie, so each rating is a single walk
+/// from the root instead of a filtering pass per bit
+pub struct Trie {
+    nodes: Vec<Node>,
+}
+
+impl Trie {
+    /// Adds every line of the report, counting the lines through each node
+    pub fn new(report: &Report) -> Self {
+        let mut nodes = vec![Node::default()];
+        for number in &report.numbers {
+            let mut node = 0;
+            nodes[node].count += 1;
+            for bit in number.iter().by_vals() {
+                let child = match nodes[node].children[usize::from(bit)] {
+                    Some(child) => child,
+                    None => {
+                        nodes.push(Node::default());
+                        nodes[node].children[usize::from(bit)] = Some(nodes.len() - 1);
+                        nodes.len() - 1
+                    }
+                };
+                node = child;
+                nodes[node].count += 1;
+            }
+        }
+        Self { nodes }
+    }
+
+    fn count(&self, node: Option<usize>) -> usize {
+        node.map_or(0, |node| self.nodes[node].count)
+    }
+
+    /// Follows the bit criteria down from the root until a single line is left,
+    /// then the rest of that line. `None` for an empty report.
+    pub fn rating(&self, life_support: LifeSupport) -> Option<Analysis> {
+        let mut node = 0;
+        if self.nodes[node].count == 0 {
+            return None;
+        }
+        let mut bits = Bits::new();
+        let mut choices = vec![];
+        loop {
+            let [zero, one] = self.nodes[node].children;
+            let (zeros, ones) = (self.count(zero), self.count(one));
+            let bit = match (zeros, ones, life_support) {
+                (0, 0, _) => break,
+                // Every line left has the same bit, so there's nothing to choose
+                (0, _, _) => true,
+                (_, 0, _) => false,
+                (_, _, LifeSupport::Oxygen) => ones >= zeros,
+                (_, _, LifeSupport::Co2) => ones < zeros,
+            };
+            choices.push(BitChoice {
+                position: bits.len(),
+                zeros,
+                ones,
+                bit,
+            });
+            bits.push(bit);
+            node = if bit { one } else { zero }.unwrap();
+        }
+        Some(Analysis { bits, choices })
+    }
+}
+
+pub fn rating(report: &Report, life_support: LifeSupport) -> Analysis {
+    Trie::new(report)
+        .rating(life_support)
+        .expect("reports have at least one line")
+}
+
 ///
-/// Get rating from largest to smallest bit search, by default it gets the oxygen scrubber rating
+/// Get rating from largest to smallest bit search, by default it gets the oxygen scrubber rating,
+/// `None` for reports wider than a `usize`
 ///
 /// ```
-/// let report = advent2021_lib::day03::Report {
-///     numbers: advent2021_lib::day03::Numbers(vec![
-///         0b1111_1100,
-///         0b1111_0000,
-///         0b1111_0100,
-///         0b1100_0000,
-///     ]),
-///     width: 8,
-/// };
+/// let report = advent2021_lib::day03::get_report("11111100\n11110000\n11110100\n11000000").unwrap();
 /// let avg = advent2021_lib::day03::get_rating(&report, advent2021_lib::day03::LifeSupport::Oxygen);
-/// assert_eq!(avg, 0b1111_0100);
+/// assert_eq!(avg, Some(0b1111_0100));
 /// ```
 ///
-pub fn get_rating(report: &Report, life_support: LifeSupport) -> usize {
-    let mut report_rows: HashSet<usize> = report.numbers.0.iter().cloned().collect();
-    let mut mask_offset = report.width;
-    log::info!(
-        "rating  {:b}",
-        Numbers(report_rows.iter().cloned().collect())
-    );
-    while report_rows.len() > 1 {
-        mask_offset -= 1;
-        let low_upper_bound_inclusive = report_rows
-            .len()
-            .checked_sub(1)
-            .unwrap()
-            .checked_div(2)
-            .unwrap();
-        let high_lower_bound_inclusive = report_rows
-            .len()
-            .checked_add(1)
-            .unwrap()
-            .checked_div(2)
-            .unwrap();
-        let bit_sum = report_rows
-            .iter()
-            .fold(0, |acc, val| acc + ((val >> mask_offset) & 1));
-        let bit_to_keep = match bit_sum {
-            s if s >= high_lower_bound_inclusive => 1,
-            s if s <= low_upper_bound_inclusive => 0,
-            _ => panic!("unexpected sum"),
-        };
-        let bit_to_keep = match life_support {
-            LifeSupport::Oxygen => bit_to_keep,
-            LifeSupport::Co2 => 1 - bit_to_keep,
-        };
-        report_rows.retain(|number| ((number >> mask_offset) & 1) == bit_to_keep);
-        log::info!(
-            "        {:b}",
-            Numbers(report_rows.iter().cloned().collect())
-        );
+pub fn get_rating(report: &Report, life_support: LifeSupport) -> Option<usize> {
+    let rating = rating(report, life_support);
+    for choice in &rating.choices {
+        log::info!("{:?} {}", life_support, choice);
     }
-    let rating = report_rows.drain().next().unwrap();
-    rating
+    to_usize(&rating.bits)
 }
 
-pub fn get_oxygen_rating(report: &Report) -> usize {
+pub fn get_oxygen_rating(report: &Report) -> Option<usize> {
     get_rating(report, LifeSupport::Oxygen)
 }
 
-pub fn get_co2_rating(report: &Report) -> usize {
+pub fn get_co2_rating(report: &Report) -> Option<usize> {
     get_rating(report, LifeSupport::Co2)
 }
 
-pub fn part1(report: &Report) -> PartOutput<usize> {
-    let gamma_rate = get_bitwise_avg(report);
-    let power_consumption = gamma_rate_to_power_rate(gamma_rate, report.width);
+pub fn part1(report: &Report) -> PartOutput<BigUint> {
+    let gamma_rate = gamma_rate(report);
+    let epsilon_rate = to_biguint(&!gamma_rate.bits.clone());
     PartOutput {
-        answer: power_consumption,
+        answer: gamma_rate.value() * epsilon_rate,
     }
 }
 
-pub fn part2(report: &Report) -> PartOutput<usize> {
-    let oxygen_rating = get_oxygen_rating(report);
-    let co2_rating = get_co2_rating(report);
-    let life_support_rating = oxygen_rating * co2_rating;
+pub fn part2(report: &Report) -> PartOutput<BigUint> {
+    let oxygen_rating = rating(report, LifeSupport::Oxygen).value();
+    let co2_rating = rating(report, LifeSupport::Co2).value();
     PartOutput {
-        answer: life_support_rating,
+        answer: oxygen_rating * co2_rating,
     }
 }
 
-pub const DAY: Day<Report, usize> = Day {
+pub const DAY: Day<Report, BigUint> = Day {
     title: "Binary Diagnostic",
     display: (
         "The power consumption of the submarine is {answer}",
@@ -225,22 +319,117 @@ pub const DAY: Day<Report, usize> = Day {
 mod tests {
     use super::*;
     use crate::get_input;
+    use crate::test_rng::Rng;
     use test_log::test;
 
     #[test]
     fn test_example_part1() {
         let report = get_report(DAY.example).unwrap();
         let result = get_bitwise_avg(&report);
-        assert_eq!(result, 22);
+        assert_eq!(result, Some(22));
     }
 
     #[test]
     fn test_example_part2() {
         let report = get_report(DAY.example).unwrap();
         let oxygen_result = get_oxygen_rating(&report);
-        assert_eq!(oxygen_result, 23);
+        assert_eq!(oxygen_result, Some(23));
         let co2_result = get_co2_rating(&report);
-        assert_eq!(co2_result, 10);
+        assert_eq!(co2_result, Some(10));
+    }
+
+    #[test]
+    fn test_example_choices() {
+        let report = get_report(DAY.example).unwrap();
+        let oxygen = rating(&report, LifeSupport::Oxygen);
+        assert_eq!(oxygen.bits.to_string(), "[1, 0, 1, 1, 1]");
+        assert_eq!(
+            oxygen.ties().collect::<Vec<_>>(),
+            [&BitChoice {
+                position: 4,
+                zeros: 1,
+                ones: 1,
+                bit: true
+            }]
+        );
+        let co2 = rating(&report, LifeSupport::Co2);
+        assert_eq!(co2.value(), BigUint::from(10u8));
+        assert_eq!(
+            co2.choices[2].to_string(),
+            "bit 2: 1 zeros, 1 ones, chose 0 on a tie"
+        );
+        assert_eq!(
+            co2.choices[3].to_string(),
+            "bit 3: 0 zeros, 1 ones, chose 1"
+        );
+        assert_eq!(gamma_rate(&report).ties().count(), 0);
+        assert_eq!(epsilon_rate(&report).load_be::<usize>(), 9);
+    }
+
+    #[test]
+    fn test_invalid_report() {
+        assert!(matches!(get_report(""), Err(ParseError::Empty)));
+        assert!(get_report("0101\n011").is_err());
+        assert!(get_report("0121").is_err());
+    }
+
+    /// The ratings by filtering the lines as strings, one pass per bit
+    fn filter_rating(lines: &[String], life_support: LifeSupport) -> String {
+        let mut lines = lines.to_vec();
+        let mut position = 0;
+        while lines.len() > 1 {
+            let ones = lines
+                .iter()
+                .filter(|line| line.as_bytes()[position] == b'1')
+                .count();
+            let zeros = lines.len() - ones;
+            let keep = match life_support {
+                LifeSupport::Oxygen if ones >= zeros => b'1',
+                LifeSupport::Co2 if ones < zeros && ones > 0 => b'1',
+                LifeSupport::Co2 if zeros == 0 => b'1',
+                _ => b'0',
+            };
+            lines.retain(|line| line.as_bytes()[position] == keep);
+            position += 1;
+        }
+        lines.remove(0)
+    }
+
+    #[test]
+    fn test_wide_reports() {
+        let mut rng = Rng::new(0x2021_0003);
+        let mut next = |max: u64| rng.below(max);
+        for _ in 0..50 {
+            let width = 1 + next(150) as usize;
+            // Few distinct prefixes so there are plenty of ties
+            let lines: Vec<String> = (0..1 + next(40))
+                .map(|_| {
+                    (0..width)
+                        .map(|_| if next(3) == 0 { '1' } else { '0' })
+                        .collect()
+                })
+                .collect();
+            let report = get_report(&lines.join("\n")).unwrap();
+            for life_support in [LifeSupport::Oxygen, LifeSupport::Co2] {
+                let bits = rating(&report, life_support).bits;
+                let rating: String = bits
+                    .iter()
+                    .map(|bit| if *bit { '1' } else { '0' })
+                    .collect();
+                assert_eq!(rating, filter_rating(&lines, life_support), "{:?}", lines);
+            }
+            let gamma = gamma_rate(&report);
+            assert_eq!(gamma.bits.len(), width);
+            assert_eq!(
+                to_biguint(&gamma.bits) + to_biguint(&epsilon_rate(&report)) + 1u8,
+                BigUint::from(1u8) << width
+            );
+            // The `usize` values agree while they fit, and are missing after that
+            let avg = get_bitwise_avg(&report).map(BigUint::from);
+            let fits = width <= usize::BITS as usize;
+            assert_eq!(avg, fits.then(|| to_biguint(&gamma.bits)));
+            assert_eq!(get_oxygen_rating(&report).is_some(), fits);
+        }
     }
 
     #[test]
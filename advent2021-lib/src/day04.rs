@@ -1,5 +1,4 @@
 use ndarray::{Array2, Axis};
-use std::collections::VecDeque;
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
@@ -21,18 +20,45 @@ pub struct Number {
     mark: Marked,
 }
 
+/// A grid of numbers of any size, each board its own, a bingo once every number in a row or
+/// column is marked
 #[derive(Clone, Debug)]
 pub struct Board(Array2<Number>);
 
 impl Board {
+    /// Rows and columns
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.0.dim()
+    }
+
+    /// Marks every copy of the number, returning whether there were any
+    pub fn mark(&mut self, number: usize) -> bool {
+        let mut found = false;
+        self.0.map_inplace(|num: &mut Number| {
+            if num.val == number {
+                num.mark = Marked::Marked;
+                found = true;
+            }
+        });
+        found
+    }
+
     pub fn is_bingo(&self) -> bool {
+        self.is_bingo_with(false)
+    }
+
+    /// Also counts either diagonal when `diag` is set, for square boards
+    pub fn is_bingo_with(&self, diag: bool) -> bool {
+        let marked = |num: &Number| matches!(num.mark, Marked::Marked);
+        let (rows, cols) = self.dimensions();
         (0..self.0.ndim()).any(|dim| {
-            self.0.axis_iter(Axis(dim)).any(|col_view| {
-                col_view
-                    .iter()
-                    .all(|num| matches!(num.mark, Marked::Marked))
-            })
-        })
+            self.0
+                .axis_iter(Axis(dim))
+                .any(|col_view| col_view.iter().all(marked))
+        }) || (diag
+            && rows == cols
+            && ((0..rows).all(|idx| marked(&self.0[[idx, idx]]))
+                || (0..rows).all(|idx| marked(&self.0[[idx, cols - 1 - idx]]))))
     }
 
     pub fn score(&self, number: usize) -> usize {
@@ -52,6 +78,32 @@ impl Board {
     }
 }
 
+/// The numbers in their grid, with a `*` after those that are marked
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .0
+            .iter()
+            .map(|num| num.val.to_string().len())
+            .max()
+            .unwrap_or(0);
+        for row in self.0.rows() {
+            let line: Vec<String> = row
+                .iter()
+                .map(|num| {
+                    let mark = match num.mark {
+                        Marked::Marked => '*',
+                        Marked::Unmarked => ' ',
+                    };
+                    format!("{:>width$}{}", num.val, mark, width = width)
+                })
+                .collect();
+            writeln!(f, "{}", line.join(" ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Bingo {
     pub numbers: Vec<usize>,
     pub boards: Vec<Board>,
@@ -62,79 +114,144 @@ pub fn get_bingo(input: &str) -> ParseResult<Bingo> {
     let numbers = lines
         .next()
         .ok_or(ParseError::Empty)?
+        .trim()
         .split(',')
         .map(|element| element.parse().map_err(ParseError::Int))
         .collect::<ParseResult<Vec<usize>>>()?;
     log::debug!("numbers: {:?}", numbers);
     let board_lines = lines
+        .map(|line| line.trim())
         .skip_while(|&line| line.is_empty())
         .collect::<Vec<&str>>();
-    let mut board_lines = board_lines.split(|line| line.is_empty()).peekable();
-    let board_size = {
-        let &first = board_lines.peek().ok_or(ParseError::Empty)?;
-        log::debug!("first board: {:#?}", first);
-        Ok((
-            first.len(),
-            first
-                .iter()
-                .next()
-                .ok_or(ParseError::Empty)?
-                .split_whitespace()
-                .count(),
-        ))
-    }?;
-    log::debug!("board size: {:?}", board_size);
     let boards = board_lines
+        .split(|line| line.is_empty())
+        .filter(|lines| !lines.is_empty())
         .map(|lines| {
-            let mut board = Board(Array2::default(board_size));
-            // TODO: not this
+            let size = (lines.len(), lines[0].split_whitespace().count());
+            let mut board = Board(Array2::default(size));
             for (row, line) in lines.iter().enumerate() {
-                for (col, val) in line.split_whitespace().enumerate() {
-                    match val.parse() {
-                        Ok(val) => {
-                            board.0[[row, col]] = Number {
-                                val,
-                                mark: Marked::Unmarked,
-                            }
-                        }
-                        Err(_) => return Err(ParseError::Empty),
+                let vals: Vec<&str> = line.split_whitespace().collect();
+                if vals.len() != size.1 {
+                    return Err(ParseError::Str(line.to_string()));
+                }
+                for (col, val) in vals.into_iter().enumerate() {
+                    board.0[[row, col]] = Number {
+                        val: val.parse().map_err(ParseError::Int)?,
+                        mark: Marked::Unmarked,
                     }
                 }
             }
             Ok(board)
         })
         .collect::<ParseResult<Vec<Board>>>()?;
+    if boards.is_empty() {
+        return Err(ParseError::Empty);
+    }
     log::debug!("boards: {:#?}", boards);
     Ok(Bingo { numbers, boards })
 }
 
-pub struct BingoResult {
-    first: (usize, Board),
-    last: (usize, Board),
+/// The game just after a number is drawn
+#[derive(Clone, Debug)]
+pub struct Draw {
+    /// Counting from 1 for the first number drawn
+    pub turn: usize,
+    pub number: usize,
+    /// Every board, those that have already won left as they were when they won
+    pub boards: Vec<Board>,
+    /// The indices of the boards that won with this number
+    pub winners: Vec<usize>,
 }
 
-// TODO: take non-ref
-pub fn play(bingo: &Bingo) -> BingoResult {
-    let Bingo { numbers, boards } = bingo;
-    let mut bingo_boards = VecDeque::<(usize, Board)>::new();
-    let mut boards: Vec<Board> = boards.clone();
-    for n in numbers {
-        log::debug!("number: {:#?}", n);
-        for board in &mut boards {
-            board.0.map_inplace(|num: &mut Number| {
-                if &num.val == n {
-                    num.mark = Marked::Marked;
-                }
-            });
+/// Marks the number on every board that hasn't won yet, returning the indices of those
+/// that win with it
+fn mark_all(boards: &mut [Board], won: &mut [bool], number: usize, diag: bool) -> Vec<usize> {
+    let mut winners = vec![];
+    for (idx, board) in boards.iter_mut().enumerate() {
+        if won[idx] {
+            continue;
+        }
+        board.mark(number);
+        if board.is_bingo_with(diag) {
+            won[idx] = true;
+            winners.push(idx);
         }
-        let (new_bingo_boards, remaining_boards) =
-            boards.into_iter().partition(|board| board.is_bingo());
-        boards = remaining_boards;
-        bingo_boards.extend(new_bingo_boards.into_iter().map(|board| (*n, board)));
     }
+    winners
+}
+
+/// Plays the numbers one at a time, copying every board on each draw to show the game
+pub struct Draws<'a> {
+    numbers: std::slice::Iter<'a, usize>,
+    turn: usize,
+    boards: Vec<Board>,
+    won: Vec<bool>,
+    diag: bool,
+}
+
+impl Iterator for Draws<'_> {
+    type Item = Draw;
+
+    fn next(&mut self) -> Option<Draw> {
+        let &number = self.numbers.next()?;
+        log::debug!("number: {:#?}", number);
+        self.turn += 1;
+        let winners = mark_all(&mut self.boards, &mut self.won, number, self.diag);
+        Some(Draw {
+            turn: self.turn,
+            number,
+            boards: self.boards.clone(),
+            winners,
+        })
+    }
+}
+
+/// A board's bingo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Win {
+    pub board: usize,
+    pub turn: usize,
+    pub number: usize,
+    pub score: usize,
+}
+
+impl Bingo {
+    pub fn draws(&self, diag: bool) -> Draws<'_> {
+        Draws {
+            numbers: self.numbers.iter(),
+            turn: 0,
+            boards: self.boards.clone(),
+            won: vec![false; self.boards.len()],
+            diag,
+        }
+    }
+
+    /// Every board that gets a bingo, in the order they win
+    pub fn wins(&self, diag: bool) -> Vec<Win> {
+        let mut boards = self.boards.clone();
+        let mut won = vec![false; boards.len()];
+        let mut wins = vec![];
+        for (idx, &number) in self.numbers.iter().enumerate() {
+            for board in mark_all(&mut boards, &mut won, number, diag) {
+                wins.push(Win {
+                    board,
+                    turn: idx + 1,
+                    number,
+                    score: boards[board].score(number),
+                });
+            }
+        }
+        wins
+    }
+}
+
+pub struct BingoResult {
+    pub wins: Vec<Win>,
+}
+
+pub fn play(bingo: &Bingo) -> BingoResult {
     BingoResult {
-        first: bingo_boards.pop_front().unwrap(),
-        last: bingo_boards.pop_back().unwrap(),
+        wins: bingo.wins(false),
     }
 }
 
@@ -144,22 +261,22 @@ pub fn parse_and_play(input: &str) -> ParseResult<BingoResult> {
 }
 
 pub fn part1(bingo_result: &BingoResult) -> PartOutput<usize> {
-    let BingoResult {
-        first: (number, board),
-        last: _last,
-    } = bingo_result;
     PartOutput {
-        answer: board.score(*number),
+        answer: bingo_result
+            .wins
+            .first()
+            .expect("the puzzle always has a winning board")
+            .score,
     }
 }
 
 pub fn part2(bingo_result: &BingoResult) -> PartOutput<usize> {
-    let BingoResult {
-        first: _first,
-        last: (number, board),
-    } = bingo_result;
     PartOutput {
-        answer: board.score(*number),
+        answer: bingo_result
+            .wins
+            .last()
+            .expect("the puzzle always has a winning board")
+            .score,
     }
 }
 
@@ -187,16 +304,96 @@ mod tests {
     fn test_example_part1() {
         let bingo = get_bingo(DAY.example).unwrap();
         let result = play(&bingo);
-        let (number, board) = result.first;
-        assert_eq!(board.score(number), 4512);
+        assert_eq!(result.wins.first().unwrap().score, 4512);
     }
 
     #[test]
     fn test_example_part2() {
         let bingo = get_bingo(DAY.example).unwrap();
         let result = play(&bingo);
-        let (number, board) = result.last;
-        assert_eq!(board.score(number), 1924);
+        assert_eq!(result.wins.last().unwrap().score, 1924);
+    }
+
+    #[test]
+    fn test_example_wins() {
+        let bingo = get_bingo(DAY.example).unwrap();
+        assert_eq!(
+            bingo.wins(false),
+            [
+                Win {
+                    board: 2,
+                    turn: 12,
+                    number: 24,
+                    score: 4512
+                },
+                Win {
+                    board: 0,
+                    turn: 14,
+                    number: 16,
+                    score: 2192
+                },
+                Win {
+                    board: 1,
+                    turn: 15,
+                    number: 13,
+                    score: 1924
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_example_draws() {
+        let bingo = get_bingo(DAY.example).unwrap();
+        let draw = bingo.draws(false).nth(11).unwrap();
+        assert_eq!((draw.turn, draw.number), (12, 24));
+        assert_eq!(draw.winners, [2]);
+        assert_eq!(
+            draw.boards[2].to_string(),
+            "14* 21* 17* 24*  4*\n\
+             10  16  15   9* 19\n\
+             18   8  23* 26  20\n\
+             22  11* 13   6   5*\n\
+             \x202*  0* 12   3   7*\n"
+        );
+        assert_eq!(bingo.draws(false).count(), bingo.numbers.len());
+        // The draws show the same wins as playing without copying the boards
+        let winners: Vec<(usize, usize)> = bingo
+            .draws(false)
+            .flat_map(|draw| {
+                draw.winners
+                    .into_iter()
+                    .map(move |board| (board, draw.turn))
+            })
+            .collect();
+        let wins: Vec<(usize, usize)> = bingo
+            .wins(false)
+            .iter()
+            .map(|win| (win.board, win.turn))
+            .collect();
+        assert_eq!(winners, wins);
+    }
+
+    #[test]
+    fn test_diagonals_and_sizes() {
+        let bingo = get_bingo("1,5,9,3\n\n1 2 3\n4 5 6\n7 8 9\n\n1 2 3 4\n6 5 7 8\n").unwrap();
+        assert_eq!(bingo.boards[0].dimensions(), (3, 3));
+        assert_eq!(bingo.boards[1].dimensions(), (2, 4));
+        // The diagonal only counts when asked for, and never on a board that isn't square
+        assert_eq!(bingo.wins(false), []);
+        assert_eq!(
+            bingo.wins(true),
+            [Win {
+                board: 0,
+                turn: 3,
+                number: 9,
+                score: (2 + 3 + 4 + 6 + 7 + 8) * 9
+            }]
+        );
+        let bingo = get_bingo("3,5,7\n\n1 2 3\n4 5 6\n7 8 9\n").unwrap();
+        assert_eq!(bingo.wins(true).len(), 1);
+        assert!(get_bingo("1\n\n1 2\n3 4 5\n").is_err());
+        assert!(get_bingo("1\n\n").is_err());
     }
 
     #[test]
@@ -205,5 +402,6 @@ mod tests {
         let result = play(&bingo);
         assert_eq!(part1(&result).answer.to_string(), "63552");
         assert_eq!(part2(&result).answer.to_string(), "9020");
+        assert_eq!(result.wins.len(), bingo.boards.len());
     }
 }
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use recap::Recap;
 use serde::Deserialize;
 
+use crate::raster::{self, Rgb};
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
 #[derive(Debug, Deserialize, Recap)]
@@ -21,10 +22,10 @@ pub struct Vents(HashMap<(usize, usize), usize>);
 
 impl std::fmt::Debug for Vents {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let min_x = self.0.keys().min_by_key(|pos| pos.0).unwrap().0;
-        let max_x = self.0.keys().max_by_key(|pos| pos.0).unwrap().0;
-        let min_y = self.0.keys().min_by_key(|pos| pos.1).unwrap().1;
-        let max_y = self.0.keys().max_by_key(|pos| pos.1).unwrap().1;
+        let ((min_x, min_y), (max_x, max_y)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return writeln!(f, "no vents"),
+        };
         for y in min_y..=max_y {
             for x in min_x..=max_x {
                 write!(
@@ -41,10 +42,162 @@ impl std::fmt::Debug for Vents {
     }
 }
 
+/// Colour for cells without vents
+const BACKGROUND: Rgb = [12, 12, 28];
+/// The heat map runs from cool to hot through these colours
+const HEAT_SCALE: [Rgb; 4] = [
+    [40, 80, 180],
+    [210, 50, 50],
+    [250, 200, 40],
+    [255, 255, 255],
+];
+
+/// The colour for a number of overlapping lines, on a scale from one line up to `max`
+pub fn heat_colour(overlaps: usize, max: usize) -> Rgb {
+    if overlaps == 0 {
+        return BACKGROUND;
+    }
+    if max <= 1 {
+        return HEAT_SCALE[0];
+    }
+    let stops = HEAT_SCALE.len() - 1;
+    // Position along the scale in units of 1 / (max - 1) of a stop
+    let pos = (overlaps.min(max) - 1) * stops;
+    let (stop, rem) = (pos / (max - 1), pos % (max - 1));
+    if stop == stops {
+        return HEAT_SCALE[stops];
+    }
+    let (from, to) = (HEAT_SCALE[stop], HEAT_SCALE[stop + 1]);
+    let mut colour = from;
+    for (channel, (from, to)) in colour.iter_mut().zip(from.iter().zip(to.iter())) {
+        *channel = ((*from as usize * (max - 1 - rem) + *to as usize * rem) / (max - 1)) as u8;
+    }
+    colour
+}
+
+fn hex(colour: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour[0], colour[1], colour[2])
+}
+
 impl Vents {
     fn count_overlap(&self) -> usize {
         self.0.values().filter(|&&v| v >= 2).count()
     }
+
+    fn bounds(&self) -> Option<((usize, usize), (usize, usize))> {
+        let mut positions = self.0.keys();
+        let first = *positions.next()?;
+        Some(positions.fold(
+            (first, first),
+            |((min_x, min_y), (max_x, max_y)), &(x, y)| {
+                ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+            },
+        ))
+    }
+
+    /// The number of lines over a position
+    pub fn get(&self, pos: &(usize, usize)) -> usize {
+        self.0.get(pos).copied().unwrap_or(0)
+    }
+
+    /// The most lines over any one position
+    pub fn max_overlap(&self) -> usize {
+        self.0.values().copied().max().unwrap_or(0)
+    }
+
+    /// Width and height of the map from the origin to the furthest vent
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.bounds()
+            .map_or((0, 0), |(_, (max_x, max_y))| (max_x + 1, max_y + 1))
+    }
+
+    /// The heat map with each position as a `scale` pixel square, row by row
+    pub fn pixels(&self, scale: usize) -> Vec<Rgb> {
+        let (width, height) = self.dimensions();
+        let max = self.max_overlap();
+        let mut pixels = Vec::with_capacity(width * height * scale * scale);
+        for y in 0..height {
+            let row: Vec<Rgb> = (0..width)
+                .flat_map(|x| vec![heat_colour(self.get(&(x, y)), max); scale])
+                .collect();
+            for _ in 0..scale {
+                pixels.extend(&row);
+            }
+        }
+        pixels
+    }
+
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        raster::to_png(width * scale, height * scale, &self.pixels(scale))
+    }
+
+    pub fn to_ppm(&self, scale: usize) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        raster::to_ppm(width * scale, height * scale, &self.pixels(scale))
+    }
+
+    /// The heat map with a key to the colours underneath. Runs of positions
+    /// along a row with the same count share a rectangle to keep the file small.
+    pub fn render_svg(&self, scale: usize) -> String {
+        let (width, height) = self.dimensions();
+        let max = self.max_overlap();
+        // The key takes a strip under the map that grows with it
+        let key = (width.max(height) / 40).max(1);
+        let key_height = key * 2;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            width * scale,
+            (height + key_height) * scale,
+            width,
+            height + key_height
+        );
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            width,
+            height + key_height,
+            hex(BACKGROUND)
+        ));
+        for y in 0..height {
+            let mut x = 0;
+            while x < width {
+                let overlaps = self.get(&(x, y));
+                let start = x;
+                while x < width && self.get(&(x, y)) == overlaps {
+                    x += 1;
+                }
+                if overlaps > 0 {
+                    svg.push_str(&format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\" fill=\"{}\"/>\n",
+                        start,
+                        y,
+                        x - start,
+                        hex(heat_colour(overlaps, max))
+                    ));
+                }
+            }
+        }
+        for overlaps in 1..=max {
+            let x = (overlaps - 1) * key * 3;
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                x,
+                height + key / 2,
+                key,
+                key,
+                hex(heat_colour(overlaps, max))
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"white\">{}</text>\n",
+                x + key + key / 4,
+                height + key * 3 / 2,
+                key,
+                overlaps
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
 }
 
 pub fn parse(input: &str) -> ParseResult<Lines> {
@@ -89,6 +242,188 @@ pub fn plot(lines: &Lines, diag: bool) -> Vents {
     vents
 }
 
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn cross(a: (i128, i128), b: (i128, i128)) -> i128 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+/// The lattice points `start + k * step` for `k` in `0..=len`
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: (i128, i128),
+    step: (i128, i128),
+    len: i128,
+}
+
+/// Segments on the same infinite line share a key, the primitive direction
+/// (pointing right, or up if vertical) and the offset across it
+type LineKey = ((i128, i128), i128);
+
+impl Segment {
+    fn new(line: &Line) -> Self {
+        let start = (line.start_x as i128, line.start_y as i128);
+        let end = (line.end_x as i128, line.end_y as i128);
+        let delta = (end.0 - start.0, end.1 - start.1);
+        let len = gcd(delta.0, delta.1);
+        let mut step = if len == 0 {
+            (1, 0)
+        } else {
+            (delta.0 / len, delta.1 / len)
+        };
+        let mut start = start;
+        if step.0 < 0 || (step.0 == 0 && step.1 < 0) {
+            step = (-step.0, -step.1);
+            start = end;
+        }
+        Self { start, step, len }
+    }
+
+    fn end(&self) -> (i128, i128) {
+        (
+            self.start.0 + self.len * self.step.0,
+            self.start.1 + self.len * self.step.1,
+        )
+    }
+
+    fn key(&self) -> LineKey {
+        (self.step, cross(self.step, self.start))
+    }
+
+    /// Index of a point on this segment's line, counting lattice points along it
+    fn index(&self, point: (i128, i128)) -> i128 {
+        let norm = self.step.0 * self.step.0 + self.step.1 * self.step.1;
+        (point.0 * self.step.0 + point.1 * self.step.1).div_euclid(norm)
+    }
+
+    fn x_range(&self) -> (i128, i128) {
+        let (a, b) = (self.start.0, self.end().0);
+        (a.min(b), a.max(b))
+    }
+
+    /// The lattice point where two non-parallel segments cross, if there is one
+    fn crossing(&self, other: &Segment) -> Option<(i128, i128)> {
+        let denominator = cross(self.step, other.step);
+        if denominator == 0 {
+            return None;
+        }
+        let offset = (other.start.0 - self.start.0, other.start.1 - self.start.1);
+        let s = cross(offset, other.step);
+        let t = cross(offset, self.step);
+        if s % denominator != 0 || t % denominator != 0 {
+            return None;
+        }
+        let (s, t) = (s / denominator, t / denominator);
+        ((0..=self.len).contains(&s) && (0..=other.len).contains(&t)).then(|| {
+            (
+                self.start.0 + s * self.step.0,
+                self.start.1 + s * self.step.1,
+            )
+        })
+    }
+}
+
+/// Counts the points where at least two lines overlap without plotting them,
+/// so it copes with huge coordinates and lines at any slope.
+///
+/// Segments on the same line are swept along it to find stretches covered
+/// twice, then segments on different lines are swept left to right and each
+/// compared only with those it overlaps horizontally to find crossings.
+///
+/// ```
+/// let lines = advent2021_lib::day05::parse("0,0 -> 4,2\n2,0 -> 2,4\n4,0 -> 0,4").unwrap();
+/// assert_eq!(advent2021_lib::day05::count_overlaps(&lines, true), 2);
+/// ```
+pub fn count_overlaps(lines: &Lines, diag: bool) -> usize {
+    let segments: Vec<Segment> = lines
+        .0
+        .iter()
+        .filter(|line| diag || line.start_x == line.end_x || line.start_y == line.end_y)
+        .map(Segment::new)
+        .collect();
+
+    let mut on_line: HashMap<LineKey, Vec<(i128, i128)>> = HashMap::new();
+    for segment in &segments {
+        let first = segment.index(segment.start);
+        on_line
+            .entry(segment.key())
+            .or_default()
+            .push((first, first + segment.len));
+    }
+    // Stretches of each line covered at least twice, in order
+    let mut doubled: HashMap<LineKey, Vec<(i128, i128)>> = HashMap::new();
+    let mut count = 0;
+    for (key, intervals) in on_line {
+        let mut events: Vec<(i128, isize)> = intervals
+            .iter()
+            .flat_map(|&(first, last)| [(first, 1), (last + 1, -1)])
+            .collect();
+        events.sort_unstable();
+        let mut covering = 0;
+        let mut stretches: Vec<(i128, i128)> = vec![];
+        let mut doubled_from = None;
+        for (index, change) in events {
+            covering += change;
+            match (covering >= 2, doubled_from) {
+                (true, None) => doubled_from = Some(index),
+                (false, Some(from)) => {
+                    stretches.push((from, index - 1));
+                    count += (index - from) as usize;
+                    doubled_from = None;
+                }
+                _ => {}
+            }
+        }
+        if !stretches.is_empty() {
+            doubled.insert(key, stretches);
+        }
+    }
+    let in_doubled = |segment: &Segment, point: (i128, i128)| {
+        doubled.get(&segment.key()).into_iter().any(|stretches| {
+            let index = segment.index(point);
+            let after = stretches.partition_point(|&(first, _)| first <= index);
+            after > 0 && stretches[after - 1].1 >= index
+        })
+    };
+
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_unstable_by_key(|&idx| segments[idx].x_range().0);
+    let mut active: Vec<usize> = vec![];
+    // The lines with a doubled stretch through each crossing, as those already counted it
+    let mut crossings: HashMap<(i128, i128), HashSet<LineKey>> = HashMap::new();
+    for idx in order {
+        let segment = &segments[idx];
+        let left = segment.x_range().0;
+        active.retain(|&other| segments[other].x_range().1 >= left);
+        for &other in &active {
+            let other = &segments[other];
+            if let Some(point) = segment.crossing(other) {
+                let counted_by = crossings.entry(point).or_default();
+                for crossing in [segment, other] {
+                    if in_doubled(crossing, point) {
+                        counted_by.insert(crossing.key());
+                    }
+                }
+            }
+        }
+        active.push(idx);
+    }
+    for counted_by in crossings.values() {
+        match counted_by.len() {
+            0 => count += 1,
+            // Where doubled stretches cross, each of them counted the point
+            lines => count -= lines - 1,
+        }
+    }
+    count
+}
+
 pub fn part1(lines: &Lines) -> PartOutput<usize> {
     let vents = plot(lines, false);
     PartOutput {
@@ -121,6 +456,7 @@ pub const DAY: Day<Lines, usize> = Day {
 mod tests {
     use super::*;
     use crate::get_input;
+    use crate::test_rng::Rng;
     use test_log::test;
 
     #[test]
@@ -135,10 +471,130 @@ mod tests {
         assert_eq!(vents.count_overlap(), 12);
     }
 
+    #[test]
+    fn test_example_count_overlaps() {
+        let lines = parse(DAY.example).unwrap();
+        assert_eq!(count_overlaps(&lines, false), 5);
+        assert_eq!(count_overlaps(&lines, true), 12);
+    }
+
+    #[test]
+    fn test_empty_map() {
+        let vents = plot(&parse("").unwrap(), true);
+        assert_eq!(format!("{:?}", vents), "no vents\n");
+        assert_eq!(vents.dimensions(), (0, 0));
+        assert_eq!(vents.max_overlap(), 0);
+    }
+
+    #[test]
+    fn test_heat_colour() {
+        assert_eq!(heat_colour(0, 5), BACKGROUND);
+        assert_eq!(heat_colour(1, 1), HEAT_SCALE[0]);
+        assert_eq!(heat_colour(1, 4), HEAT_SCALE[0]);
+        assert_eq!(heat_colour(2, 4), HEAT_SCALE[1]);
+        assert_eq!(heat_colour(4, 4), HEAT_SCALE[3]);
+        assert_eq!(heat_colour(9, 4), HEAT_SCALE[3]);
+        assert_eq!(heat_colour(2, 3), [230, 125, 45]);
+    }
+
+    #[test]
+    fn test_example_images() {
+        let vents = plot(&parse(DAY.example).unwrap(), true);
+        assert_eq!(vents.dimensions(), (10, 10));
+        assert_eq!(vents.max_overlap(), 3);
+        let pixels = vents.pixels(2);
+        assert_eq!(pixels.len(), 20 * 20);
+        // (4, 4) is covered by three lines, (0, 0) by one
+        assert_eq!(pixels[8 * 20 + 9], HEAT_SCALE[3]);
+        assert_eq!(pixels[0], HEAT_SCALE[0]);
+        assert_eq!(pixels[2], BACKGROUND);
+        assert!(vents.to_ppm(2).starts_with(b"P6\n20 20\n255\n"));
+        assert_eq!(&vents.to_png(2)[16..24], &[0, 0, 0, 20, 0, 0, 0, 20]);
+        let svg = vents.render_svg(10);
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"120\""));
+        // The top row is 1.1.....11 with the key for 1 to 3 lines
+        assert!(svg.contains("<rect x=\"7\" y=\"0\" width=\"2\" height=\"1\" fill=\"#2850b4\"/>"));
+        assert_eq!(svg.matches("<text").count(), 3);
+    }
+
+    /// Every lattice point of every line, for lines at any slope
+    fn plot_lattice(lines: &Lines) -> usize {
+        let mut counts: HashMap<(i128, i128), usize> = HashMap::new();
+        for segment in lines.0.iter().map(Segment::new) {
+            for k in 0..=segment.len {
+                let point = (
+                    segment.start.0 + k * segment.step.0,
+                    segment.start.1 + k * segment.step.1,
+                );
+                *counts.entry(point).or_default() += 1;
+            }
+        }
+        counts.values().filter(|&&count| count >= 2).count()
+    }
+
+    #[test]
+    fn test_count_overlaps_any_slope() {
+        let mut rng = Rng::new(0x2021_0005);
+        let mut next = |max: u64| rng.below(max);
+        for _ in 0..200 {
+            let lines: Vec<Line> = (0..1 + next(12))
+                .map(|_| {
+                    let (x, y) = (next(16), next(16));
+                    // Mostly lines along the axes and diagonals, so some overlap
+                    let (dx, dy) = match next(3) {
+                        0 => (next(16) as i64 - x as i64, 0),
+                        1 => {
+                            let d = next(10) as i64;
+                            (d, if next(2) == 0 { d } else { -d })
+                        }
+                        _ => (next(16) as i64 - x as i64, next(16) as i64 - y as i64),
+                    };
+                    // Built directly, parsing would recompile the line regex every time
+                    Line {
+                        start_x: x as usize,
+                        start_y: y as usize,
+                        end_x: (x as i64 + dx).max(0) as usize,
+                        end_y: (y as i64 + dy).max(0) as usize,
+                    }
+                })
+                .collect();
+            let lines = Lines(lines);
+            assert_eq!(
+                count_overlaps(&lines, true),
+                plot_lattice(&lines),
+                "{:#?}",
+                lines.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_overlaps_crossing_doubled() {
+        // Two doubled diagonals crossing at 3,3
+        let lines = parse("0,0 -> 6,6\n1,1 -> 5,5\n0,6 -> 6,0\n1,5 -> 5,1").unwrap();
+        assert_eq!(count_overlaps(&lines, true), 9);
+        assert_eq!(count_overlaps(&lines, true), plot_lattice(&lines));
+    }
+
+    #[test]
+    fn test_count_overlaps_huge() {
+        let lines = parse(
+            "0,0 -> 1000000000000,1000000000000\n\
+             0,1000000000000 -> 1000000000000,0\n\
+             0,500000000000 -> 1000000000000,500000000000\n\
+             0,0 -> 3000000000,3000000000",
+        )
+        .unwrap();
+        assert_eq!(count_overlaps(&lines, true), 3000000002);
+    }
+
     #[test]
     fn test_main() {
         let vents = parse(&get_input(5)).unwrap();
         assert_eq!(part1(&vents).answer.to_string(), "4873");
         assert_eq!(part2(&vents).answer.to_string(), "19472");
+        assert_eq!(count_overlaps(&vents, false), 4873);
+        assert_eq!(count_overlaps(&vents, true), 19472);
     }
 }
@@ -1,18 +1,24 @@
 use ndarray::prelude::*;
 
+pub use crate::matrix::{Count, Matrix};
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
 #[derive(Clone, Default, Debug)]
 pub struct LanternfishState(pub Vec<usize>);
 
 pub fn parse(input: &str) -> ParseResult<LanternfishState> {
-    Ok(LanternfishState(
-        input
-            .trim()
-            .split(',')
-            .map(|val| val.parse().map_err(ParseError::Int))
-            .collect::<ParseResult<_>>()?,
-    ))
+    let state: Vec<usize> = input
+        .trim()
+        .split(',')
+        .map(|val| val.parse().map_err(ParseError::Int))
+        .collect::<ParseResult<_>>()?;
+    if let Some(fish) = state
+        .iter()
+        .find(|&&fish| fish >= PopulationModel::LANTERNFISH.timers())
+    {
+        return Err(ParseError::Str(TimerOutOfRange(*fish).to_string()));
+    }
+    Ok(LanternfishState(state))
 }
 
 /// One day of lanternfish.
@@ -96,13 +102,138 @@ pub fn fish_count_ndarray(state: &[usize], n: usize) -> usize {
     counts.into_iter().sum()
 }
 
+/// Counts modulo `M`, for populations too big to count exactly
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Modular<const M: u64>(u64);
+
+impl<const M: u64> Modular<M> {
+    /// Fails to compile for `Modular<0>`, which would divide by zero
+    const NONZERO: () = assert!(M != 0, "cannot count modulo 0");
+
+    pub fn new(value: u64) -> Self {
+        let () = Self::NONZERO;
+        Self(value % M)
+    }
+
+    /// The count, always less than `M`
+    pub fn value(self) -> u64 {
+        self.0
+    }
+}
+
+impl<const M: u64> std::ops::Add for Modular<M> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self(((self.0 as u128 + other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> std::ops::Mul for Modular<M> {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self(((self.0 as u128 * other.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> num_traits::Zero for Modular<M> {
+    fn zero() -> Self {
+        Self::new(0)
+    }
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl<const M: u64> num_traits::One for Modular<M> {
+    fn one() -> Self {
+        Self::new(1)
+    }
+}
+
+impl<const M: u64> std::fmt::Display for Modular<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (mod {})", self.0, M)
+    }
+}
+
+/// A fish's timer is higher than any the model uses
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimerOutOfRange(pub usize);
+
+impl std::fmt::Display for TimerOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timer {} is out of range for the model", self.0)
+    }
+}
+
+/// Fish whose timers count down each day, and at 0 reset to `reset`
+/// and spawn a new fish with a timer of `newborn`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PopulationModel {
+    pub reset: usize,
+    pub newborn: usize,
+}
+
+impl PopulationModel {
+    pub const LANTERNFISH: Self = Self {
+        reset: 6,
+        newborn: 8,
+    };
+
+    /// Number of distinct timer values
+    pub fn timers(&self) -> usize {
+        self.reset.max(self.newborn) + 1
+    }
+
+    /// Maps the counts of fish with each timer to the counts a day later
+    pub fn transition<T: Count>(&self) -> Matrix<T> {
+        let mut matrix = Matrix::zeros(self.timers());
+        for timer in 1..self.timers() {
+            matrix[(timer - 1, timer)] = T::one();
+        }
+        matrix[(self.reset, 0)] = matrix[(self.reset, 0)].clone() + T::one();
+        matrix[(self.newborn, 0)] = matrix[(self.newborn, 0)].clone() + T::one();
+        matrix
+    }
+
+    pub fn counts<T: Count>(&self, state: &[usize]) -> Result<Vec<T>, TimerOutOfRange> {
+        let mut counts = vec![T::zero(); self.timers()];
+        for &fish in state {
+            let count = counts.get_mut(fish).ok_or(TimerOutOfRange(fish))?;
+            *count = count.clone() + T::one();
+        }
+        Ok(counts)
+    }
+
+    /// The number of fish after `days`, in O(log days) matrix multiplications
+    ///
+    /// ```
+    /// use advent2021_lib::day06::PopulationModel;
+    /// let count: usize = PopulationModel::LANTERNFISH.population(&[3, 4, 3, 1, 2], 18).unwrap();
+    /// assert_eq!(count, 26);
+    /// ```
+    pub fn population<T: Count>(&self, state: &[usize], days: u64) -> Result<T, TimerOutOfRange> {
+        let counts = self.counts(state)?;
+        Ok(self
+            .transition()
+            .pow(days)
+            .apply(&counts)
+            .into_iter()
+            .fold(T::zero(), |acc, count| acc + count))
+    }
+}
+
 pub fn part1(state: &LanternfishState) -> PartOutput<usize> {
-    let count = fish_count_array(&state.0, 80);
+    let count = PopulationModel::LANTERNFISH
+        .population(&state.0, 80)
+        .expect("timers are checked when parsing");
     PartOutput { answer: count }
 }
 
 pub fn part2(state: &LanternfishState) -> PartOutput<usize> {
-    let count = fish_count_array(&state.0, 256);
+    let count = PopulationModel::LANTERNFISH
+        .population(&state.0, 256)
+        .expect("timers are checked when parsing");
     PartOutput { answer: count }
 }
 
@@ -124,6 +255,8 @@ pub const DAY: Day<LanternfishState, usize> = Day {
 mod tests {
     use super::*;
     use crate::get_input;
+    use num_bigint::BigUint;
+    use num_traits::Zero;
     use test_log::test;
 
     #[test]
@@ -154,6 +287,56 @@ mod tests {
         assert_eq!(result, 26984457539);
     }
 
+    /// Steps the counts one day at a time, to check the matrix against
+    fn population_by_day(model: &PopulationModel, state: &[usize], days: u64) -> BigUint {
+        let mut counts: Vec<BigUint> = model.counts(state).unwrap();
+        for _ in 0..days {
+            let spawning = counts.remove(0);
+            counts.push(BigUint::from(0u8));
+            counts[model.reset] += &spawning;
+            counts[model.newborn] += spawning;
+        }
+        counts.into_iter().sum()
+    }
+
+    #[test]
+    fn test_example_population() {
+        let state = parse(DAY.example).unwrap();
+        let model = PopulationModel::LANTERNFISH;
+        assert_eq!(model.population::<usize>(&state.0, 256), Ok(26984457539));
+        let big: BigUint = model.population(&state.0, 5000).unwrap();
+        assert_eq!(big, population_by_day(&model, &state.0, 5000));
+        assert!(big.to_string().len() > 100);
+        const M: u64 = 1_000_000_007;
+        let modular: Modular<M> = model.population(&state.0, 5000).unwrap();
+        assert_eq!(BigUint::from(modular.value()), big % M);
+        assert!(Modular::<7>::new(7).is_zero());
+        assert_eq!(Modular::<7>::new(12), Modular::new(5));
+        assert_eq!(model.population::<usize>(&[9], 1), Err(TimerOutOfRange(9)));
+    }
+
+    #[test]
+    fn test_other_model() {
+        let model = PopulationModel {
+            reset: 2,
+            newborn: 4,
+        };
+        assert_eq!(model.timers(), 5);
+        let state = [0, 1, 4, 2];
+        for days in [0, 1, 2, 7, 30] {
+            assert_eq!(
+                model.population::<BigUint>(&state, days).unwrap(),
+                population_by_day(&model, &state, days)
+            );
+        }
+        // Counting exactly takes a moment, modulo a prime takes no time at all
+        const M: u64 = 998_244_353;
+        let big: BigUint = model.population(&state, 100_000).unwrap();
+        let modular: Modular<M> = model.population(&state, 100_000).unwrap();
+        assert_eq!(BigUint::from(modular.value()), big % M);
+        assert!(parse("3,9").is_err());
+    }
+
     #[test]
     fn test_main() {
         let state = parse(&get_input(6)).unwrap();
@@ -13,48 +13,109 @@ pub fn parse(input: &str) -> ParseResult<CrabPositions> {
     ))
 }
 
-pub fn get_const_fuel(positions: &CrabPositions) -> usize {
-    let CrabPositions(mut positions) = positions.clone();
-    // If the length is even, we get 2 medians. Both have the same fuel cost.
-    let median_idx = positions.len() / 2;
-    positions.select_nth_unstable(median_idx);
-    let median = positions[median_idx];
-    positions
-        .iter()
-        .fold(0, |acc, val| acc + val.abs_diff(median))
+/// Where the crabs line up, and the fuel it takes them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Alignment {
+    pub position: usize,
+    pub fuel: usize,
 }
 
-fn get_fuel(positions: &[usize], target: usize) -> usize {
-    positions.iter().fold(0, |acc, val| {
-        acc + (1..=val.abs_diff(target)).sum::<usize>()
-    })
+/// The fuel a crab burns to move some distance.
+///
+/// Costs must be convex and non-decreasing in the distance, so the total fuel
+/// has no local minima other than the global one.
+pub trait FuelCost {
+    fn cost(&self, distance: usize) -> usize;
+
+    fn total(&self, positions: &[usize], target: usize) -> usize {
+        positions
+            .iter()
+            .map(|pos| self.cost(pos.abs_diff(target)))
+            .sum()
+    }
+
+    /// The cheapest position between the outermost crabs, the leftmost on ties
+    fn optimise(&self, positions: &[usize]) -> Option<Alignment> {
+        let mut lo = *positions.iter().min()?;
+        let mut hi = *positions.iter().max()?;
+        while hi - lo > 2 {
+            let third = (hi - lo) / 3;
+            let (m1, m2) = (lo + third, hi - third);
+            match self.total(positions, m1).cmp(&self.total(positions, m2)) {
+                std::cmp::Ordering::Less => hi = m2 - 1,
+                std::cmp::Ordering::Equal => hi = m2,
+                std::cmp::Ordering::Greater => lo = m1 + 1,
+            }
+        }
+        self.best_of(positions, lo..=hi)
+    }
+
+    fn best_of(
+        &self,
+        positions: &[usize],
+        candidates: impl Iterator<Item = usize>,
+    ) -> Option<Alignment> {
+        candidates
+            .map(|position| Alignment {
+                position,
+                fuel: self.total(positions, position),
+            })
+            .min_by_key(|alignment| (alignment.fuel, alignment.position))
+    }
 }
 
-pub fn get_linear_fuel(positions: &CrabPositions) -> usize {
-    let CrabPositions(positions) = positions.clone();
-    // Probably an OK starting point
-    let mean = (positions.iter().sum::<usize>() + positions.len() / 2) / positions.len();
-    let mut best_pos = mean;
-    let mut best_fuel = get_fuel(&positions, best_pos);
-    // search up
-    loop {
-        let next_fuel = get_fuel(&positions, best_pos + 1);
-        if next_fuel > best_fuel {
-            break;
+/// Every step costs one fuel
+#[derive(Clone, Copy, Debug)]
+pub struct Constant;
+
+impl FuelCost for Constant {
+    fn cost(&self, distance: usize) -> usize {
+        distance
+    }
+
+    fn optimise(&self, positions: &[usize]) -> Option<Alignment> {
+        if positions.is_empty() {
+            return None;
         }
-        best_fuel = next_fuel;
-        best_pos += 1;
+        // Any position between the two medians is optimal, take the lower one
+        let mut positions = positions.to_vec();
+        let median_idx = (positions.len() - 1) / 2;
+        let (_, &mut median, _) = positions.select_nth_unstable(median_idx);
+        self.best_of(&positions, std::iter::once(median))
     }
-    //search down
-    loop {
-        let next_fuel = get_fuel(&positions, best_pos - 1);
-        if next_fuel > best_fuel {
-            break;
+}
+
+/// Each step costs one more fuel than the last
+#[derive(Clone, Copy, Debug)]
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: usize) -> usize {
+        distance * (distance + 1) / 2
+    }
+
+    fn optimise(&self, positions: &[usize]) -> Option<Alignment> {
+        if positions.is_empty() {
+            return None;
         }
-        best_fuel = next_fuel;
-        best_pos += 1;
+        // The optimum is always within half a step of the mean
+        let mean = positions.iter().sum::<usize>() / positions.len();
+        self.best_of(positions, mean.saturating_sub(1)..=mean + 1)
+    }
+}
+
+impl<F: Fn(usize) -> usize> FuelCost for F {
+    fn cost(&self, distance: usize) -> usize {
+        self(distance)
     }
-    best_fuel
+}
+
+pub fn get_const_fuel(positions: &CrabPositions) -> usize {
+    Constant.optimise(&positions.0).map_or(0, |a| a.fuel)
+}
+
+pub fn get_linear_fuel(positions: &CrabPositions) -> usize {
+    Triangular.optimise(&positions.0).map_or(0, |a| a.fuel)
 }
 
 pub fn part1(positions: &CrabPositions) -> PartOutput<usize> {
@@ -85,6 +146,7 @@ pub const DAY: Day<CrabPositions, usize> = Day {
 mod tests {
     use super::*;
     use crate::get_input;
+    use crate::test_rng::Rng;
     use test_log::test;
 
     #[test]
@@ -101,6 +163,72 @@ mod tests {
         assert_eq!(result, 168);
     }
 
+    #[test]
+    fn test_example_alignment() {
+        let positions = parse(DAY.example).unwrap();
+        assert_eq!(
+            Constant.optimise(&positions.0),
+            Some(Alignment {
+                position: 2,
+                fuel: 37
+            })
+        );
+        assert_eq!(
+            Triangular.optimise(&positions.0),
+            Some(Alignment {
+                position: 5,
+                fuel: 168
+            })
+        );
+        assert_eq!(Triangular.optimise(&[]), None);
+    }
+
+    fn random_positions(rng: &mut Rng) -> Vec<usize> {
+        let len = 1 + rng.below(12);
+        let spread = 1 + rng.below(40);
+        (0..len).map(|_| rng.below(spread) as usize).collect()
+    }
+
+    fn brute_force(cost: &impl FuelCost, positions: &[usize]) -> Option<Alignment> {
+        let min = *positions.iter().min()?;
+        let max = *positions.iter().max()?;
+        cost.best_of(positions, min..=max)
+    }
+
+    #[test]
+    fn test_optimise_matches_brute_force() {
+        let cubic = |d: usize| d * d * d;
+        let stepped = |d: usize| d.saturating_sub(3) * 2;
+        let mut rng = Rng::new(0x2021_0007);
+        for _ in 0..500 {
+            let positions = random_positions(&mut rng);
+            assert_eq!(
+                Constant.optimise(&positions),
+                brute_force(&Constant, &positions),
+                "{:?}",
+                positions
+            );
+            assert_eq!(
+                Triangular.optimise(&positions),
+                brute_force(&Triangular, &positions),
+                "{:?}",
+                positions
+            );
+            assert_eq!(
+                cubic.optimise(&positions),
+                brute_force(&cubic, &positions),
+                "{:?}",
+                positions
+            );
+            assert_eq!(
+                stepped.optimise(&positions),
+                brute_force(&stepped, &positions),
+                "{:?}",
+                positions
+            );
+        }
+    }
+
     #[test]
     fn test_main() {
         let positions = parse(&get_input(7)).unwrap();
@@ -1,6 +1,3 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
-
 use recap::Recap;
 use serde::Deserialize;
 
@@ -13,36 +10,56 @@ pub struct DisplayObservationsParse {
     output: String,
 }
 
-#[derive(Clone, Default, Debug)]
+/// Set of wires or segments, bit 0 for `a` up to bit 6 for `g`
+type Segments = u8;
+
+const SEGMENT_COUNT: usize = 7;
+
+/// Segments lit for each number, as on an unscrambled display
+const NUMBER_SEGMENTS: [Segments; 10] = [
+    0b1110111, // 0: abcefg
+    0b0100100, // 1: cf
+    0b1011101, // 2: acdeg
+    0b1101101, // 3: acdfg
+    0b0101110, // 4: bcdf
+    0b1101011, // 5: abdfg
+    0b1111011, // 6: abdefg
+    0b0100101, // 7: acf
+    0b1111111, // 8: abcdefg
+    0b1101111, // 9: abcdfg
+];
+
+fn segments_to_number(segments: Segments) -> Option<usize> {
+    NUMBER_SEGMENTS.iter().position(|s| *s == segments)
+}
+
+fn segment_char(segment: usize) -> char {
+    (b'a' + segment as u8) as char
+}
+
+/// The wires lit for a single digit
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
 pub struct Digit {
-    segments: Vec<char>,
+    wires: Segments,
 }
 
 impl std::str::FromStr for Digit {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Digit {
-            segments: s.chars().collect(),
-        })
+        let mut wires = 0;
+        for c in s.chars() {
+            match c {
+                'a'..='g' => wires |= 1 << (c as u8 - b'a'),
+                _ => return Err(ParseError::Str(s.to_owned())),
+            }
+        }
+        Ok(Digit { wires })
     }
 }
 
-impl std::convert::From<Digit> for HashSet<usize> {
-    fn from(digit: Digit) -> Self {
-        let mut values = HashSet::new();
-        for val in digit.segments.into_iter().map(|segment| match segment {
-            'a' => 0,
-            'b' => 1,
-            'c' => 2,
-            'd' => 3,
-            'e' => 4,
-            'f' => 5,
-            'g' => 6,
-            _ => panic!("unknown segment"),
-        }) {
-            values.insert(val);
-        }
-        values
+impl Digit {
+    fn len(&self) -> usize {
+        self.wires.count_ones() as usize
     }
 }
 
@@ -54,44 +71,74 @@ impl std::str::FromStr for Digits {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(Digits(
             s.split(' ')
-                .map(|digit| digit.parse().map_err(|_| ParseError::Str(digit.to_owned())))
+                .map(|digit| digit.parse())
                 .collect::<ParseResult<_>>()?,
         ))
     }
 }
 
-struct Wires(Vec<usize>);
+/// Which segment each wire drives
+#[derive(Clone, Copy, PartialEq)]
+pub struct Wiring([usize; SEGMENT_COUNT]);
+
+impl Wiring {
+    /// The segment driven by `wire`, both as `a` to `g`
+    pub fn segment(&self, wire: char) -> Option<char> {
+        let wire = (wire as usize).checked_sub('a' as usize)?;
+        self.0.get(wire).map(|segment| segment_char(*segment))
+    }
+
+    fn segments(&self, wires: Segments) -> Segments {
+        (0..SEGMENT_COUNT)
+            .filter(|wire| wires & 1 << wire != 0)
+            .fold(0, |segments, wire| segments | 1 << self.0[wire])
+    }
+
+    /// The wire driving `segment`, both as `a` to `g`
+    pub fn wire(&self, segment: char) -> Option<char> {
+        let segment = (segment as usize).checked_sub('a' as usize)?;
+        self.0.iter().position(|s| *s == segment).map(segment_char)
+    }
+
+    /// Number shown by a digit, if the wires light up a valid one
+    pub fn decode(&self, digit: &Digit) -> Option<usize> {
+        segments_to_number(self.segments(digit.wires))
+    }
+}
 
-impl From<&Wires> for Vec<char> {
-    fn from(wires: &Wires) -> Self {
-        wires
+/// Printed as `wire->segment` pairs, e.g. `a->c b->f ...`
+impl std::fmt::Display for Wiring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs: Vec<String> = self
             .0
             .iter()
-            .map(|wire| match wire {
-                0 => 'a',
-                1 => 'b',
-                2 => 'c',
-                3 => 'd',
-                4 => 'e',
-                5 => 'f',
-                6 => 'g',
-                _ => panic!("bad wire"),
-            })
-            .collect()
+            .enumerate()
+            .map(|(wire, segment)| format!("{}->{}", segment_char(wire), segment_char(*segment)))
+            .collect();
+        write!(f, "{}", pairs.join(" "))
     }
 }
 
-impl std::fmt::Debug for Wires {
+impl std::fmt::Debug for Wiring {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            Vec::<char>::from(self)
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>()
-                .join("")
-        )
+        write!(f, "{}", self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SolveError {
+    /// No wiring lights up valid numbers for every observed digit
+    Unsolvable,
+    /// More than one wiring fits the observed digits
+    Ambiguous,
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsolvable => write!(f, "no wiring fits the display"),
+            Self::Ambiguous => write!(f, "more than one wiring fits the display"),
+        }
     }
 }
 
@@ -121,9 +168,261 @@ impl std::str::FromStr for DisplayObservation {
     }
 }
 
+impl DisplayObservation {
+    /// Every distinct pattern of wires seen on the display
+    fn patterns(&self) -> Vec<Segments> {
+        let mut patterns: Vec<Segments> = self
+            .observations
+            .0
+            .iter()
+            .chain(self.output.0.iter())
+            .map(|digit| digit.wires)
+            .collect();
+        patterns.sort_unstable();
+        patterns.dedup();
+        patterns
+    }
+
+    /// Deduce which segment each wire drives
+    pub fn solve(&self) -> Result<Wiring, SolveError> {
+        let patterns = self.patterns();
+        // Numbers each pattern may show, as a bitmask over 0 to 9
+        let mut numbers: Vec<u16> = patterns
+            .iter()
+            .map(|pattern| {
+                (0..NUMBER_SEGMENTS.len())
+                    .filter(|number| NUMBER_SEGMENTS[*number].count_ones() == pattern.count_ones())
+                    .fold(0, |numbers, number| numbers | 1 << number)
+            })
+            .collect();
+        // Patterns known to show different numbers must overlap like those numbers do
+        loop {
+            let mut changed = false;
+            for (idx, pattern) in patterns.iter().enumerate() {
+                for (known_idx, known) in patterns.iter().enumerate() {
+                    if idx == known_idx || numbers[known_idx].count_ones() != 1 {
+                        continue;
+                    }
+                    let known_number = numbers[known_idx].trailing_zeros() as usize;
+                    let overlap = (pattern & known).count_ones();
+                    let remaining = (0..NUMBER_SEGMENTS.len())
+                        .filter(|number| {
+                            numbers[idx] & 1 << number != 0
+                                && *number != known_number
+                                && (NUMBER_SEGMENTS[*number] & NUMBER_SEGMENTS[known_number])
+                                    .count_ones()
+                                    == overlap
+                        })
+                        .fold(0, |numbers, number| numbers | 1 << number);
+                    if remaining != numbers[idx] {
+                        numbers[idx] = remaining;
+                        changed = true;
+                    }
+                }
+            }
+            if numbers.contains(&0) {
+                return Err(SolveError::Unsolvable);
+            }
+            if !changed {
+                break;
+            }
+        }
+        log::debug!("numbers: {:?}", numbers);
+        // Wires lit for a known number drive its segments, the others do not
+        let mut candidates: [Segments; SEGMENT_COUNT] = [0b1111111; SEGMENT_COUNT];
+        for (pattern, numbers) in patterns.iter().zip(&numbers) {
+            if numbers.count_ones() != 1 {
+                continue;
+            }
+            let segments = NUMBER_SEGMENTS[numbers.trailing_zeros() as usize];
+            for (wire, candidate) in candidates.iter_mut().enumerate() {
+                if pattern & 1 << wire != 0 {
+                    *candidate &= segments;
+                } else {
+                    *candidate &= !segments & 0b1111111;
+                }
+            }
+        }
+        log::debug!("candidates: {:?}", candidates);
+        // Search whatever propagation left open
+        let mut solutions = Vec::new();
+        search_wiring(
+            &patterns,
+            &candidates,
+            &mut [0; SEGMENT_COUNT],
+            0,
+            0,
+            &mut solutions,
+        );
+        match solutions.as_slice() {
+            [] => Err(SolveError::Unsolvable),
+            [wiring] => Ok(*wiring),
+            _ => Err(SolveError::Ambiguous),
+        }
+    }
+
+    /// Decode the four output digits as a single number
+    pub fn decode(&self) -> Result<usize, SolveError> {
+        let wiring = self.solve()?;
+        log::debug!("wiring: {}", wiring);
+        Ok(self
+            .output
+            .0
+            .iter()
+            .map(|digit| wiring.decode(digit).unwrap())
+            .fold(0, |acc, val| acc * 10 + val))
+    }
+}
+
+/// Assign segments to wires from `wire` onwards, stopping after two solutions
+fn search_wiring(
+    patterns: &[Segments],
+    candidates: &[Segments; SEGMENT_COUNT],
+    assigned: &mut [usize; SEGMENT_COUNT],
+    wire: usize,
+    used: Segments,
+    solutions: &mut Vec<Wiring>,
+) {
+    if solutions.len() > 1 {
+        return;
+    }
+    if wire == SEGMENT_COUNT {
+        let wiring = Wiring(*assigned);
+        if patterns
+            .iter()
+            .all(|pattern| segments_to_number(wiring.segments(*pattern)).is_some())
+        {
+            solutions.push(wiring);
+        }
+        return;
+    }
+    for segment in 0..SEGMENT_COUNT {
+        let bit = 1 << segment;
+        if candidates[wire] & bit != 0 && used & bit == 0 {
+            assigned[wire] = segment;
+            search_wiring(
+                patterns,
+                candidates,
+                assigned,
+                wire + 1,
+                used | bit,
+                solutions,
+            );
+        }
+    }
+}
+
+/// Rows of the ASCII art for one digit, with segment letters where the segment is drawn
+const ASCII_DIGIT: [&str; 7] = [
+    " aaaa ", //
+    "b    c", //
+    "b    c", //
+    " dddd ", //
+    "e    f", //
+    "e    f", //
+    " gggg ", //
+];
+
+/// Corners of each segment in the SVG of one digit, `a` to `g`
+const SVG_SEGMENTS: [(u32, u32, u32, u32); SEGMENT_COUNT] = [
+    (14, 4, 46, 4),
+    (10, 8, 10, 40),
+    (50, 8, 50, 40),
+    (14, 44, 46, 44),
+    (10, 48, 10, 80),
+    (50, 48, 50, 80),
+    (14, 84, 46, 84),
+];
+
+impl DisplayObservation {
+    /// Segments lit for each output digit, and the wiring that lights them
+    fn output_segments(&self) -> Result<(Wiring, Vec<Segments>), SolveError> {
+        let wiring = self.solve()?;
+        let segments = self
+            .output
+            .0
+            .iter()
+            .map(|digit| wiring.segments(digit.wires))
+            .collect();
+        Ok((wiring, segments))
+    }
+
+    /// Draw the output digits side by side in seven segment style,
+    /// lit segments as `#`, or as the scrambled wire driving them with `highlight_wires`
+    pub fn render_ascii(&self, highlight_wires: bool) -> Result<String, SolveError> {
+        let (wiring, outputs) = self.output_segments()?;
+        let rows: Vec<String> = ASCII_DIGIT
+            .iter()
+            .map(|row| {
+                outputs
+                    .iter()
+                    .map(|segments| {
+                        row.chars()
+                            .map(|c| match c {
+                                ' ' => ' ',
+                                segment if segments & 1 << (segment as u8 - b'a') == 0 => '.',
+                                segment if highlight_wires => wiring.wire(segment).unwrap(),
+                                _ => '#',
+                            })
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<String>>()
+                    .join("  ")
+            })
+            .collect();
+        Ok(rows.join("\n") + "\n")
+    }
+
+    /// Draw the output digits as an inline SVG,
+    /// labelling lit segments with the scrambled wire driving them with `highlight_wires`
+    pub fn render_svg(&self, highlight_wires: bool) -> Result<String, SolveError> {
+        const WIDTH: u32 = 60;
+        const HEIGHT: u32 = 88;
+        let (wiring, outputs) = self.output_segments()?;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            WIDTH * outputs.len() as u32,
+            HEIGHT,
+            WIDTH * outputs.len() as u32,
+            HEIGHT
+        );
+        for (idx, segments) in outputs.iter().enumerate() {
+            let offset = WIDTH * idx as u32;
+            for (segment, (x1, y1, x2, y2)) in SVG_SEGMENTS.iter().enumerate() {
+                let lit = segments & 1 << segment != 0;
+                svg.push_str(&format!(
+                    "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"6\" stroke-linecap=\"round\"/>\n",
+                    offset + x1,
+                    y1,
+                    offset + x2,
+                    y2,
+                    if lit { "#ffff66" } else { "#333340" }
+                ));
+                if lit && highlight_wires {
+                    svg.push_str(&format!(
+                        "<text x=\"{}\" y=\"{}\" font-size=\"10\" font-family=\"monospace\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"#cc0000\">{}</text>\n",
+                        offset + (x1 + x2) / 2,
+                        (y1 + y2) / 2,
+                        wiring.wire(segment_char(segment)).unwrap()
+                    ));
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct DisplayObservations(Vec<DisplayObservation>);
 
+impl DisplayObservations {
+    /// The deduced wiring of each display
+    pub fn wirings(&self) -> Vec<Result<Wiring, SolveError>> {
+        self.0.iter().map(|display| display.solve()).collect()
+    }
+}
+
 pub fn parse(input: &str) -> ParseResult<DisplayObservations> {
     Ok(DisplayObservations(
         input
@@ -133,22 +432,6 @@ pub fn parse(input: &str) -> ParseResult<DisplayObservations> {
     ))
 }
 
-fn number_to_segments(number: usize) -> HashSet<usize> {
-    match number {
-        0 => HashSet::from([0, 1, 2, 4, 5, 6]),
-        1 => HashSet::from([2, 5]),
-        2 => HashSet::from([0, 2, 3, 4, 6]),
-        3 => HashSet::from([0, 2, 3, 5, 6]),
-        4 => HashSet::from([1, 2, 3, 5]),
-        5 => HashSet::from([0, 1, 3, 5, 6]),
-        6 => HashSet::from([0, 1, 3, 4, 5, 6]),
-        7 => HashSet::from([0, 2, 5]),
-        8 => HashSet::from([0, 1, 2, 3, 4, 5, 6]),
-        9 => HashSet::from([0, 1, 2, 3, 5, 6]),
-        n => panic!("unexpected number {}", n),
-    }
-}
-
 const fn segment_count_to_number(number: usize) -> Option<usize> {
     match number {
         2 => Some(1), // 1
@@ -168,109 +451,17 @@ pub fn count_1s_4s_7s_8s(observations: &DisplayObservations) -> usize {
                 .output
                 .0
                 .iter()
-                .filter_map(|digit| segment_count_to_number(digit.segments.len()))
+                .filter_map(|digit| segment_count_to_number(digit.len()))
         })
         .flatten()
         .count()
 }
 
-pub fn get_outputs(display_observations: &DisplayObservations) -> Vec<usize> {
+pub fn get_outputs(display_observations: &DisplayObservations) -> Result<Vec<usize>, SolveError> {
     display_observations
         .0
         .iter()
-        .map(|display| {
-            let measurements = {
-                let mut m: Vec<Digit> = display.observations.0.clone();
-                m.extend(display.output.0.clone());
-                m
-            };
-            let wire_to_segments = {
-                let mut wire_to_segments = HashMap::new();
-                for wire in 0..7 {
-                    wire_to_segments.insert(wire, HashSet::from([0, 1, 2, 3, 4, 5, 6, 7]));
-                }
-                for measurement in &measurements {
-                    let segment_count = measurement.segments.len();
-                    let measurement_wires: HashSet<usize> = measurement.clone().into();
-                    if let Some(number) = segment_count_to_number(segment_count) {
-                        for wire in measurement_wires {
-                            wire_to_segments.entry(wire).and_modify(|segments| {
-                                *segments = segments
-                                    .intersection(&number_to_segments(number))
-                                    .cloned()
-                                    .collect();
-                            });
-                        }
-                    }
-                }
-                wire_to_segments
-            };
-            log::debug!("wire_to_segments: {:?}", wire_to_segments);
-            let wire_to_segment_permutations = {
-                let mut permutations: Vec<Vec<usize>> = vec![vec![]];
-                for wire in 0..7 {
-                    let last_layer: Vec<Vec<usize>> = permutations.drain(..).collect();
-                    permutations = wire_to_segments
-                        .get(&wire)
-                        .unwrap()
-                        .iter()
-                        .map(|&segment: &usize| {
-                            last_layer
-                                .iter()
-                                .filter_map(|p: &Vec<usize>| {
-                                    if p.contains(&segment) {
-                                        return None;
-                                    }
-                                    let new_permutation: Vec<usize> =
-                                        p.iter().cloned().chain(std::iter::once(segment)).collect();
-                                    Some(new_permutation)
-                                })
-                                .collect::<Vec<Vec<usize>>>()
-                        })
-                        .flatten()
-                        .collect();
-                    log::debug!("permutations: {:?}", permutations);
-                }
-                permutations
-            };
-            log::debug!(
-                "wire_to_segment_permutations: {:?}",
-                wire_to_segment_permutations.len()
-            );
-            let mut best_wire_to_segment: Vec<usize> = vec![];
-            for wire_to_segment in wire_to_segment_permutations {
-                if measurements.iter().all(|measurement| {
-                    let measurement_wires: HashSet<usize> = measurement.clone().into();
-                    let measurement_segments: HashSet<usize> = measurement_wires
-                        .into_iter()
-                        .map(|wire| wire_to_segment[wire])
-                        .collect();
-                    (0..=9).any(|number| measurement_segments == number_to_segments(number))
-                }) {
-                    best_wire_to_segment = wire_to_segment;
-                    break;
-                }
-            }
-            log::debug!("best_wire_to_segment: {:?}", best_wire_to_segment);
-            let output: usize = display
-                .output
-                .0
-                .iter()
-                .map(|output| {
-                    let output_wires: HashSet<usize> = output.clone().into();
-                    let output_segments: HashSet<usize> = output_wires
-                        .into_iter()
-                        .map(|wire| best_wire_to_segment[wire])
-                        .collect();
-                    (0..=9)
-                        .find(|&number| output_segments == number_to_segments(number))
-                        .unwrap()
-                })
-                .fold((0, 1000), |acc, val| (acc.0 + acc.1 * val, acc.1 / 10))
-                .0;
-            log::debug!("output: {:?}", output);
-            output
-        })
+        .map(|display| display.decode())
         .collect()
 }
 
@@ -282,7 +473,10 @@ pub fn part1(display_observations: &DisplayObservations) -> PartOutput<usize> {
 
 pub fn part2(display_observations: &DisplayObservations) -> PartOutput<usize> {
     PartOutput {
-        answer: get_outputs(display_observations).iter().sum::<usize>(),
+        answer: get_outputs(display_observations)
+            .expect("display cannot be decoded")
+            .iter()
+            .sum::<usize>(),
     }
 }
 
@@ -316,10 +510,90 @@ mod tests {
     #[test]
     fn test_example_part2() {
         let display_observations = parse(DAY.example).unwrap();
-        let result = get_outputs(&display_observations);
+        let result = get_outputs(&display_observations).unwrap();
         assert_eq!(result.iter().sum::<usize>(), 61229);
     }
 
+    #[test]
+    fn test_example_wiring() {
+        let display: DisplayObservation =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf"
+                .parse()
+                .unwrap();
+        let wiring = display.solve().unwrap();
+        assert_eq!(wiring.to_string(), "a->c b->f c->g d->a e->b f->d g->e");
+        assert_eq!(wiring.segment('d'), Some('a'));
+        assert_eq!(wiring.segment('z'), None);
+        assert_eq!(display.decode(), Ok(5353));
+        let display_observations = parse(DAY.example).unwrap();
+        assert!(display_observations
+            .wirings()
+            .iter()
+            .all(|wiring| wiring.is_ok()));
+    }
+
+    #[test]
+    fn test_example_render() {
+        let display: DisplayObservation =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            display.render_ascii(false).unwrap(),
+            [
+                " ####    ####    ####    #### ",
+                "#    .  .    #  #    .  .    #",
+                "#    .  .    #  #    .  .    #",
+                " ####    ####    ####    #### ",
+                ".    #  .    #  .    #  .    #",
+                ".    #  .    #  .    #  .    #",
+                " ####    ####    ####    #### ",
+                "",
+            ]
+            .join("\n")
+        );
+        let highlighted = display.render_ascii(true).unwrap();
+        assert_eq!(
+            highlighted.lines().next(),
+            Some(" dddd    dddd    dddd    dddd ")
+        );
+        assert_eq!(
+            highlighted.lines().nth(1),
+            Some("e    .  .    a  e    .  .    a")
+        );
+        let svg = display.render_svg(true).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<line").count(), 4 * 7);
+        assert_eq!(svg.matches("#ffff66").count(), 5 * 4);
+        assert_eq!(svg.matches("<text").count(), 5 * 4);
+        assert_eq!(
+            display.render_svg(false).unwrap().matches("<text").count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_unsolvable() {
+        // Two digits with 2 segments cannot both be a 1
+        let display: DisplayObservation = "ab cd | ab ab ab ab".parse().unwrap();
+        assert_eq!(display.solve(), Err(SolveError::Unsolvable));
+        let display: DisplayObservation = "ab abc abcd | abcde abcde abcde abcde".parse().unwrap();
+        assert_eq!(display.solve(), Err(SolveError::Unsolvable));
+        assert!("abh | ab ab ab ab".parse::<DisplayObservation>().is_err());
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        // Only a 1 and an 8 leave the other wires free
+        let display: DisplayObservation = "ab abcdefg | ab ab ab abcdefg".parse().unwrap();
+        assert_eq!(display.solve(), Err(SolveError::Ambiguous));
+        let display_observations = DisplayObservations(vec![display]);
+        assert_eq!(
+            get_outputs(&display_observations),
+            Err(SolveError::Ambiguous)
+        );
+    }
+
     #[test]
     fn test_main() {
         let something = parse(&get_input(8)).unwrap();
@@ -1,121 +1,192 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
+pub type Position = (usize, usize);
+
+/// Cells this high are the ridges between basins
+const RIDGE: usize = 9;
+
 #[derive(Clone, Default, Debug)]
 pub struct Heightmap {
-    map: HashMap<(usize, usize), usize>,
+    map: HashMap<Position, usize>,
+    width: usize,
+    height: usize,
 }
 
 pub fn parse(input: &str) -> ParseResult<Heightmap> {
     let mut map = HashMap::new();
+    let (mut width, mut height) = (0, 0);
     for (row_idx, row) in input.lines().enumerate() {
         for (col_idx, num) in row.chars().enumerate() {
             map.insert(
                 (col_idx, row_idx),
                 num.to_string().parse().map_err(ParseError::Int)?,
             );
+            width = width.max(col_idx + 1);
         }
+        height = row_idx + 1;
     }
-    Ok(Heightmap { map })
+    Ok(Heightmap { map, width, height })
 }
 
-pub fn get_risk(heightmap: &Heightmap) -> usize {
-    let mut low_points: Vec<(usize, usize)> = vec![];
-    for (pos, val) in heightmap.map.iter() {
-        if val
-            >= heightmap
-                .map
-                .get(&(pos.0.wrapping_sub(1), pos.1))
-                .unwrap_or(&usize::MAX)
-            || val
-                >= heightmap
-                    .map
-                    .get(&(pos.0 + 1, pos.1))
-                    .unwrap_or(&usize::MAX)
-            || val
-                >= heightmap
-                    .map
-                    .get(&(pos.0, pos.1.wrapping_sub(1)))
-                    .unwrap_or(&usize::MAX)
-            || val
-                >= heightmap
-                    .map
-                    .get(&(pos.0, pos.1 + 1))
-                    .unwrap_or(&usize::MAX)
-        {
-            continue;
-        }
-        log::debug!("low point: {:?}", pos);
-        low_points.push(*pos);
+/// A region of the heightmap bounded by ridges and the edge of the map
+#[derive(Clone, Debug, PartialEq)]
+pub struct Basin {
+    /// The lowest cell, the first in reading order if the floor is a plateau
+    pub low_point: Position,
+    /// Every cell of the basin in reading order
+    pub cells: Vec<Position>,
+}
+
+impl Basin {
+    pub fn size(&self) -> usize {
+        self.cells.len()
     }
-    low_points
-        .iter()
-        .map(|point| heightmap.map.get(point).unwrap() + 1)
-        .sum::<usize>()
 }
 
-pub fn basin_walk(heightmap: &Heightmap) -> usize {
-    let mut basins: HashMap<(usize, usize), usize> = HashMap::new();
-    for (pos, _) in heightmap.map.iter() {
-        let mut walk = *pos;
-        loop {
-            let from_height = heightmap.map.get(&(walk.0, walk.1)).unwrap();
-            if *from_height == 9 {
-                break;
-            }
-            log::debug!("walk from {:?} at {:?}", walk, from_height);
-            // N
-            if from_height
-                >= heightmap
-                    .map
-                    .get(&(walk.0, walk.1.wrapping_sub(1)))
-                    .unwrap_or(&usize::MAX)
-            {
-                walk = (walk.0, walk.1 - 1);
-                continue;
-            }
-            // W
-            if from_height
-                >= heightmap
-                    .map
-                    .get(&(walk.0.wrapping_sub(1), walk.1))
-                    .unwrap_or(&usize::MAX)
-            {
-                walk = (walk.0 - 1, walk.1);
-                continue;
+impl Heightmap {
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, pos: &Position) -> Option<usize> {
+        self.map.get(pos).copied()
+    }
+
+    fn neighbours(&self, (x, y): Position) -> impl Iterator<Item = Position> + '_ {
+        [
+            x.checked_sub(1).map(|x| (x, y)),
+            Some((x + 1, y)),
+            y.checked_sub(1).map(|y| (x, y)),
+            Some((x, y + 1)),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|pos| self.map.contains_key(pos))
+    }
+
+    /// Cells strictly lower than all of their neighbours
+    pub fn low_points(&self) -> Vec<Position> {
+        let mut low_points: Vec<Position> = self
+            .map
+            .iter()
+            .filter(|(pos, height)| {
+                self.neighbours(**pos)
+                    .all(|neighbour| self.map[&neighbour] > **height)
+            })
+            .map(|(pos, _)| *pos)
+            .collect();
+        low_points.sort_unstable_by_key(|&(x, y)| (y, x));
+        low_points
+    }
+
+    /// Labels each cell with the index of its basin in `basins`, ridges are `None`
+    pub fn basin_labels(&self) -> Vec<Vec<Option<usize>>> {
+        let mut labels = vec![vec![None; self.width]; self.height];
+        let mut next_label = 0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if labels[y][x].is_some() || !matches!(self.get(&(x, y)), Some(h) if h < RIDGE) {
+                    continue;
+                }
+                labels[y][x] = Some(next_label);
+                let mut stack = vec![(x, y)];
+                while let Some(pos) = stack.pop() {
+                    for (nx, ny) in self.neighbours(pos) {
+                        if labels[ny][nx].is_none() && self.map[&(nx, ny)] < RIDGE {
+                            labels[ny][nx] = Some(next_label);
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+                next_label += 1;
             }
-            // S (strict)
-            if from_height
-                > heightmap
-                    .map
-                    .get(&(walk.0, walk.1 + 1))
-                    .unwrap_or(&usize::MAX)
-            {
-                walk = (walk.0, walk.1 + 1);
-                continue;
+        }
+        labels
+    }
+
+    /// Every basin, ordered by the first cell of each in reading order
+    pub fn basins(&self) -> Vec<Basin> {
+        let mut cells: BTreeMap<usize, Vec<Position>> = BTreeMap::new();
+        for (y, row) in self.basin_labels().into_iter().enumerate() {
+            for (x, label) in row.into_iter().enumerate() {
+                if let Some(label) = label {
+                    cells.entry(label).or_default().push((x, y));
+                }
             }
-            // E (strict)
-            if from_height
-                > heightmap
-                    .map
-                    .get(&(walk.0 + 1, walk.1))
-                    .unwrap_or(&usize::MAX)
-            {
-                walk = (walk.0 + 1, walk.1);
-                continue;
+        }
+        cells
+            .into_values()
+            .map(|cells| Basin {
+                low_point: *cells.iter().min_by_key(|pos| self.map[pos]).unwrap(),
+                cells,
+            })
+            .collect()
+    }
+
+    /// One character per cell, a letter per basin and `9` for ridges
+    pub fn render_ascii(&self) -> String {
+        self.basin_labels()
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|label| match label {
+                        Some(label) => (b'a' + (label % 26) as u8) as char,
+                        None => '9',
+                    })
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    /// Basins as coloured cells, shaded by height, with ridges in black
+    pub fn render_svg(&self) -> String {
+        const CELL: usize = 10;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            self.width * CELL,
+            self.height * CELL,
+            self.width * CELL,
+            self.height * CELL
+        );
+        for (y, row) in self.basin_labels().into_iter().enumerate() {
+            for (x, label) in row.into_iter().enumerate() {
+                let height = self.get(&(x, y)).unwrap_or(RIDGE);
+                let fill = match label {
+                    // Spread hues with the golden angle so neighbouring basins differ
+                    Some(label) => format!("hsl({},70%,{}%)", (label * 137) % 360, 35 + height * 5),
+                    None => "black".to_owned(),
+                };
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{}</title></rect>\n",
+                    x * CELL,
+                    y * CELL,
+                    CELL,
+                    CELL,
+                    fill,
+                    height
+                ));
             }
-            break;
         }
-        basins
-            .entry(walk)
-            .and_modify(|count| *count += 1)
-            .or_insert(1);
-    }
-    let mut sizes: Vec<usize> = basins.iter().map(|(_basin, size)| *size).collect();
-    sizes.sort_unstable();
-    log::debug!("basins: {:?}", basins);
-    sizes.pop().unwrap() * sizes.pop().unwrap() * sizes.pop().unwrap()
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+pub fn get_risk(heightmap: &Heightmap) -> usize {
+    heightmap
+        .low_points()
+        .iter()
+        .map(|point| heightmap.map[point] + 1)
+        .sum::<usize>()
+}
+
+pub fn largest_basins(heightmap: &Heightmap) -> usize {
+    let mut sizes: Vec<usize> = heightmap.basins().iter().map(Basin::size).collect();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes.iter().take(3).product()
 }
 
 pub fn part1(heightmap: &Heightmap) -> PartOutput<usize> {
@@ -126,7 +197,7 @@ pub fn part1(heightmap: &Heightmap) -> PartOutput<usize> {
 
 pub fn part2(heightmap: &Heightmap) -> PartOutput<usize> {
     PartOutput {
-        answer: basin_walk(heightmap),
+        answer: largest_basins(heightmap),
     }
 }
 
@@ -160,10 +231,44 @@ mod tests {
     #[test]
     fn test_example_part2() {
         let heightmap = parse(DAY.example).unwrap();
-        let result = basin_walk(&heightmap);
+        let result = largest_basins(&heightmap);
         assert_eq!(result, 1134);
     }
 
+    #[test]
+    fn test_example_basins() {
+        let heightmap = parse(DAY.example).unwrap();
+        let basins: Vec<(Position, usize)> = heightmap
+            .basins()
+            .iter()
+            .map(|basin| (basin.low_point, basin.size()))
+            .collect();
+        assert_eq!(
+            basins,
+            vec![((1, 0), 3), ((9, 0), 9), ((2, 2), 14), ((6, 4), 9)]
+        );
+        assert_eq!(
+            heightmap.render_ascii(),
+            "aa999bbbbb\n\
+             a9ccc9b9bb\n\
+             9ccccc9d9b\n\
+             ccccc9ddd9\n\
+             9c999ddddd\n"
+        );
+    }
+
+    #[test]
+    fn test_plateau_basin() {
+        let heightmap = parse("1119\n1999\n9922").unwrap();
+        assert_eq!(heightmap.low_points(), vec![]);
+        let basins = heightmap.basins();
+        assert_eq!(basins.len(), 2);
+        assert_eq!(basins[0].low_point, (0, 0));
+        assert_eq!(basins[0].size(), 4);
+        assert_eq!(basins[1].low_point, (2, 2));
+        assert_eq!(basins[1].size(), 2);
+    }
+
     #[test]
     fn test_main() {
         let something = parse(&get_input(9)).unwrap();
@@ -1,4 +1,6 @@
-use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
+use std::collections::HashMap;
+
+use crate::{Day, DayCalc, ParseResult, PartOutput};
 
 #[derive(Clone, Debug)]
 pub struct Lines(Vec<String>);
@@ -7,149 +9,236 @@ pub fn parse(input: &str) -> ParseResult<Lines> {
     Ok(Lines(input.lines().map(|line| line.to_owned()).collect()))
 }
 
-#[derive(Clone, Debug)]
-enum ChunkPair {
-    Paren,
-    Square,
-    Curly,
-    Angle,
+/// A matched pair of delimiters with the scores the checker assigns to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delimiter {
+    pub open: char,
+    pub close: char,
+    /// Score for finding this closer where another was expected
+    pub syntax_score: usize,
+    /// Score for having to append this closer to complete a line
+    pub completion_score: usize,
 }
 
-impl ChunkPair {
-    fn syntax_score(&self) -> usize {
-        match self {
-            Self::Paren => 3,
-            Self::Square => 57,
-            Self::Curly => 1197,
-            Self::Angle => 25137,
+impl Delimiter {
+    pub const fn new(
+        open: char,
+        close: char,
+        syntax_score: usize,
+        completion_score: usize,
+    ) -> Self {
+        Self {
+            open,
+            close,
+            syntax_score,
+            completion_score,
         }
     }
-    fn completion_score(&self) -> usize {
+}
+
+/// The chunk delimiters of the navigation subsystem
+pub const CHUNKS: [Delimiter; 4] = [
+    Delimiter::new('(', ')', 3, 1),
+    Delimiter::new('[', ']', 57, 2),
+    Delimiter::new('{', '}', 1197, 3),
+    Delimiter::new('<', '>', 25137, 4),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LanguageError {
+    /// The same character is used by more than one delimiter
+    Duplicate(char),
+    /// A delimiter opens and closes with the same character
+    Symmetric(char),
+}
+
+impl std::fmt::Display for LanguageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Paren => 1,
-            Self::Square => 2,
-            Self::Curly => 3,
-            Self::Angle => 4,
+            Self::Duplicate(c) => write!(f, "{:?} is used by more than one delimiter", c),
+            Self::Symmetric(c) => write!(f, "{:?} cannot both open and close a delimiter", c),
         }
     }
 }
 
-#[derive(Clone, Debug)]
-enum ChunkPart {
-    Open,
-    Close,
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Open(usize),
+    Close(usize),
 }
 
+/// A bracket language: a set of delimiters that must nest properly
 #[derive(Clone, Debug)]
-struct Chunk {
-    pair: ChunkPair,
-    part: ChunkPart,
-}
-
-impl std::str::FromStr for Chunk {
-    type Err = ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "(" => Chunk {
-                pair: ChunkPair::Paren,
-                part: ChunkPart::Open,
-            },
-            ")" => Chunk {
-                pair: ChunkPair::Paren,
-                part: ChunkPart::Close,
-            },
-            "[" => Chunk {
-                pair: ChunkPair::Square,
-                part: ChunkPart::Open,
-            },
-            "]" => Chunk {
-                pair: ChunkPair::Square,
-                part: ChunkPart::Close,
-            },
-            "{" => Chunk {
-                pair: ChunkPair::Curly,
-                part: ChunkPart::Open,
-            },
-            "}" => Chunk {
-                pair: ChunkPair::Curly,
-                part: ChunkPart::Close,
-            },
-            "<" => Chunk {
-                pair: ChunkPair::Angle,
-                part: ChunkPart::Open,
-            },
-            ">" => Chunk {
-                pair: ChunkPair::Angle,
-                part: ChunkPart::Close,
-            },
-            _ => return Err(ParseError::Empty),
-        })
-    }
-}
-
-pub fn corrupted_score(line: &str) -> Option<usize> {
-    log::debug!("corrupted?: {:?}", line);
-    let mut heap: Vec<ChunkPair> = vec![];
-    for char in line.chars() {
-        log::debug!("heap: {:?}", heap);
-        let chunk: Chunk = char.to_string().parse().unwrap();
-        match chunk.part {
-            ChunkPart::Open => {
-                heap.push(chunk.pair);
+pub struct Language {
+    delimiters: Vec<Delimiter>,
+    tokens: HashMap<char, Token>,
+}
+
+impl Language {
+    pub fn new(delimiters: Vec<Delimiter>) -> Result<Self, LanguageError> {
+        let mut tokens = HashMap::new();
+        for (idx, delimiter) in delimiters.iter().enumerate() {
+            if delimiter.open == delimiter.close {
+                return Err(LanguageError::Symmetric(delimiter.open));
             }
-            ChunkPart::Close => {
-                let last = heap.pop().unwrap();
-                if std::mem::discriminant(&chunk.pair) == std::mem::discriminant(&last) {
-                    continue;
-                } else {
-                    return Some(chunk.pair.syntax_score());
+            for (c, token) in [
+                (delimiter.open, Token::Open(idx)),
+                (delimiter.close, Token::Close(idx)),
+            ] {
+                if tokens.insert(c, token).is_some() {
+                    return Err(LanguageError::Duplicate(c));
                 }
             }
         }
+        Ok(Self { delimiters, tokens })
+    }
+
+    pub fn delimiters(&self) -> &[Delimiter] {
+        &self.delimiters
+    }
+
+    /// Checks a line, stopping at the first corrupted or unknown character.
+    /// Characters that aren't delimiters are ignored when `skip_unknown` is set.
+    pub fn check_with(&self, line: &str, skip_unknown: bool) -> LineStatus {
+        let mut open: Vec<usize> = vec![];
+        for (column, found) in line.chars().enumerate().map(|(idx, c)| (idx + 1, c)) {
+            match self.tokens.get(&found) {
+                Some(Token::Open(idx)) => open.push(*idx),
+                Some(Token::Close(idx)) => match open.pop() {
+                    Some(last) if last == *idx => {}
+                    last => {
+                        return LineStatus::Corrupted {
+                            column,
+                            expected: last.map(|last| self.delimiters[last].close),
+                            found,
+                        }
+                    }
+                },
+                None if skip_unknown => {}
+                None => return LineStatus::Unknown { column, found },
+            }
+        }
+        if open.is_empty() {
+            LineStatus::Valid
+        } else {
+            LineStatus::Incomplete {
+                completion: open
+                    .into_iter()
+                    .rev()
+                    .map(|idx| self.delimiters[idx].close)
+                    .collect(),
+            }
+        }
+    }
+
+    /// Checks a line that should consist only of delimiters
+    pub fn check(&self, line: &str) -> LineStatus {
+        self.check_with(line, false)
+    }
+
+    fn closing(&self, c: char) -> Option<&Delimiter> {
+        match self.tokens.get(&c) {
+            Some(Token::Close(idx)) => Some(&self.delimiters[*idx]),
+            _ => None,
+        }
+    }
+
+    /// The syntax error score of a corrupted line, otherwise `None`
+    pub fn syntax_score(&self, status: &LineStatus) -> Option<usize> {
+        match status {
+            LineStatus::Corrupted { found, .. } => self.closing(*found).map(|d| d.syntax_score),
+            _ => None,
+        }
+    }
+
+    /// The completion score of an incomplete line, otherwise `None`
+    pub fn completion_score(&self, status: &LineStatus) -> Option<usize> {
+        match status {
+            LineStatus::Incomplete { completion } => Some(completion.chars().fold(0, |acc, c| {
+                acc * 5 + self.closing(c).map_or(0, |d| d.completion_score)
+            })),
+            _ => None,
+        }
     }
-    None
 }
 
-pub fn corrupted_scores(lines: &Lines) -> usize {
-    lines
-        .0
-        .iter()
-        .filter_map(|line| corrupted_score(line))
-        .sum()
+impl Default for Language {
+    fn default() -> Self {
+        Self::new(CHUNKS.to_vec()).unwrap()
+    }
+}
+
+/// The result of checking one line. Columns count characters from 1.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LineStatus {
+    /// Every delimiter is closed by its partner
+    Valid,
+    /// A closer doesn't match the innermost open delimiter, or there was nothing open
+    Corrupted {
+        column: usize,
+        expected: Option<char>,
+        found: char,
+    },
+    /// Every closer matched, but some delimiters were left open
+    Incomplete { completion: String },
+    /// A character that isn't part of the language
+    Unknown { column: usize, found: char },
 }
 
-pub fn incomplete_score(line: &str) -> usize {
-    log::debug!("incomplete: {:?}", line);
-    let mut heap: Vec<ChunkPair> = vec![];
-    for char in line.chars() {
-        log::debug!("heap: {:?}", heap);
-        let chunk: Chunk = char.to_string().parse().unwrap();
-        match chunk.part {
-            ChunkPart::Open => {
-                heap.push(chunk.pair);
+impl std::fmt::Display for LineStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Valid => write!(f, "valid"),
+            Self::Corrupted {
+                column,
+                expected: Some(expected),
+                found,
+            } => write!(
+                f,
+                "corrupted at column {}: expected {:?}, but found {:?} instead",
+                column, expected, found
+            ),
+            Self::Corrupted {
+                column,
+                expected: None,
+                found,
+            } => write!(
+                f,
+                "corrupted at column {}: found {:?} with nothing open",
+                column, found
+            ),
+            Self::Incomplete { completion } => {
+                write!(f, "incomplete, complete by adding {}", completion)
             }
-            ChunkPart::Close => {
-                let last = heap.pop().unwrap();
-                if std::mem::discriminant(&chunk.pair) == std::mem::discriminant(&last) {
-                    continue;
-                } else {
-                    panic!("Corrupted");
-                }
+            Self::Unknown { column, found } => {
+                write!(f, "unknown character {:?} at column {}", found, column)
             }
         }
     }
-    heap.into_iter()
-        .rev()
-        .fold(0, |acc, pair| (acc * 5) + pair.completion_score())
+}
+
+pub fn check_lines<'a>(
+    language: &'a Language,
+    lines: &'a Lines,
+) -> impl Iterator<Item = LineStatus> + 'a {
+    lines.0.iter().map(move |line| language.check(line))
+}
+
+pub fn corrupted_scores(lines: &Lines) -> usize {
+    let language = Language::default();
+    check_lines(&language, lines)
+        .filter_map(|status| language.syntax_score(&status))
+        .sum()
 }
 
 pub fn incomplete_scores(lines: &Lines) -> usize {
-    let mut scores: Vec<usize> = lines
-        .0
-        .iter()
-        .filter(|line| corrupted_score(line).is_none())
-        .map(|line| incomplete_score(line))
+    let language = Language::default();
+    let mut scores: Vec<usize> = check_lines(&language, lines)
+        .filter_map(|status| language.completion_score(&status))
         .collect();
+    if scores.is_empty() {
+        return 0;
+    }
     let median_idx = scores.len() / 2;
     scores.select_nth_unstable(median_idx);
     scores[median_idx]
@@ -201,6 +290,72 @@ mod tests {
         assert_eq!(result, 288957);
     }
 
+    #[test]
+    fn test_example_statuses() {
+        let lines = parse(DAY.example).unwrap();
+        let language = Language::default();
+        let statuses: Vec<LineStatus> = check_lines(&language, &lines).collect();
+        assert_eq!(
+            statuses[0],
+            LineStatus::Incomplete {
+                completion: "}}]])})]".to_string()
+            }
+        );
+        assert_eq!(
+            statuses[2],
+            LineStatus::Corrupted {
+                column: 13,
+                expected: Some(']'),
+                found: '}'
+            }
+        );
+        assert_eq!(
+            statuses[2].to_string(),
+            "corrupted at column 13: expected ']', but found '}' instead"
+        );
+        assert_eq!(language.completion_score(&statuses[0]), Some(288957));
+    }
+
+    #[test]
+    fn test_custom_language() {
+        let language = Language::new(vec![
+            Delimiter::new('(', ')', 1, 1),
+            Delimiter::new('«', '»', 2, 2),
+        ])
+        .unwrap();
+        assert_eq!(
+            language.check_with("(let «x» (f x))", true),
+            LineStatus::Valid
+        );
+        assert_eq!(
+            language.check("(x)"),
+            LineStatus::Unknown {
+                column: 2,
+                found: 'x'
+            }
+        );
+        assert_eq!(
+            language.check("()»"),
+            LineStatus::Corrupted {
+                column: 3,
+                expected: None,
+                found: '»'
+            }
+        );
+        assert_eq!(
+            Language::new(vec![
+                Delimiter::new('(', ')', 1, 1),
+                Delimiter::new('[', ')', 1, 1)
+            ])
+            .unwrap_err(),
+            LanguageError::Duplicate(')')
+        );
+        assert_eq!(
+            Language::new(vec![Delimiter::new('|', '|', 1, 1)]).unwrap_err(),
+            LanguageError::Symmetric('|')
+        );
+    }
+
     #[test]
     fn test_main() {
         let something = parse(&get_input(10)).unwrap();
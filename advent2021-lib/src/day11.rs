@@ -1,20 +1,22 @@
 use std::collections::HashMap;
-use std::collections::HashSet;
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
-#[derive(Clone, Default)]
+/// The energy level at which an octopus flashes
+const FLASH: u8 = 10;
+
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
 pub struct EnergyLevels {
-    map: HashMap<(isize, isize), usize>,
+    width: usize,
+    height: usize,
+    levels: Vec<u8>,
 }
 
-impl std::fmt::Debug for EnergyLevels {
+impl std::fmt::Display for EnergyLevels {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let max_x = self.map.keys().max_by_key(|pos| pos.0).unwrap().0;
-        let max_y = self.map.keys().max_by_key(|pos| pos.1).unwrap().1;
-        for y in 0..max_y {
-            for x in 0..max_x {
-                write!(f, "{}", self.map.get(&(x, y)).unwrap())?;
+        for row in self.levels.chunks(self.width) {
+            for level in row {
+                write!(f, "{}", level)?;
             }
             writeln!(f)?;
         }
@@ -22,82 +24,300 @@ impl std::fmt::Debug for EnergyLevels {
     }
 }
 
+impl std::fmt::Debug for EnergyLevels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
 pub fn parse(input: &str) -> ParseResult<EnergyLevels> {
-    let mut map: HashMap<(isize, isize), usize> = HashMap::new();
-    for (row_idx, row) in input.lines().enumerate() {
-        for (col_idx, num) in row.chars().enumerate() {
-            map.insert(
-                (col_idx.try_into().unwrap(), row_idx.try_into().unwrap()),
-                num.to_string().parse().map_err(ParseError::Int)?,
-            );
+    let width = input.lines().next().map_or(0, str::len);
+    if width == 0 {
+        return Err(ParseError::Empty);
+    }
+    let mut levels = vec![];
+    for row in input.lines() {
+        if row.len() != width {
+            return Err(ParseError::Str(format!("ragged row {:?}", row)));
+        }
+        for num in row.chars() {
+            let level = num
+                .to_digit(10)
+                .ok_or_else(|| ParseError::Str(format!("invalid energy level {:?}", num)))?;
+            levels.push(level as u8);
         }
     }
-    Ok(EnergyLevels { map })
+    Ok(EnergyLevels {
+        width,
+        height: levels.len() / width,
+        levels,
+    })
 }
 
-pub fn flash_step(energy_levels: &mut EnergyLevels) -> usize {
-    let mut flashed: HashSet<(isize, isize)> = HashSet::new();
-    let mut to_flash_stack: Vec<(isize, isize)> = vec![];
-    for (_, energy_level) in energy_levels.map.iter_mut() {
-        *energy_level += 1;
-    }
-    loop {
-        log::debug!("energy_levels:\n{:#?}", energy_levels);
-        for (pos, energy_level) in energy_levels.map.iter_mut() {
-            if *energy_level > 9 && !flashed.contains(pos) {
-                to_flash_stack.push(*pos);
+impl EnergyLevels {
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn levels(&self) -> &[u8] {
+        &self.levels
+    }
+
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width, self.height);
+        let (x, y) = (idx % width, idx / width);
+        (y.saturating_sub(1)..=(y + 1).min(height - 1)).flat_map(move |ny| {
+            (x.saturating_sub(1)..=(x + 1).min(width - 1))
+                .filter(move |&nx| (nx, ny) != (x, y))
+                .map(move |nx| ny * width + nx)
+        })
+    }
+
+    /// Iterates over the following steps, starting with step 1
+    pub fn simulate(&self) -> Simulation {
+        Simulation {
+            levels: self.clone(),
+            step: 0,
+        }
+    }
+
+    /// Finds when the octopuses start repeating themselves, giving up after `max_steps`
+    pub fn cycle(&self, max_steps: usize) -> Option<Cycle> {
+        let mut seen: HashMap<EnergyLevels, usize> = HashMap::new();
+        seen.insert(self.clone(), 0);
+        let mut first_sync = None;
+        for frame in self.simulate().take(max_steps) {
+            if frame.synchronised() && first_sync.is_none() {
+                first_sync = Some(frame.step);
+            }
+            if let Some(start) = seen.insert(frame.levels, frame.step) {
+                return Some(Cycle {
+                    start,
+                    period: frame.step - start,
+                    first_sync,
+                });
             }
         }
-        if to_flash_stack.is_empty() {
-            break;
+        None
+    }
+}
+
+pub fn flash_step(energy_levels: &mut EnergyLevels) -> usize {
+    let mut to_flash: Vec<usize> = vec![];
+    for (idx, level) in energy_levels.levels.iter_mut().enumerate() {
+        *level += 1;
+        if *level == FLASH {
+            to_flash.push(idx);
         }
-        while let Some(to_flash) = to_flash_stack.pop() {
-            flashed.insert(to_flash);
-            for x in -1..=1 {
-                for y in -1..=1 {
-                    if let Some(energy_level) =
-                        energy_levels.map.get_mut(&(to_flash.0 + x, to_flash.1 + y))
-                    {
-                        *energy_level += 1;
-                    }
-                }
+    }
+    let mut flashes = 0;
+    while let Some(idx) = to_flash.pop() {
+        flashes += 1;
+        for neighbour in energy_levels.neighbours(idx) {
+            let level = &mut energy_levels.levels[neighbour];
+            *level += 1;
+            if *level == FLASH {
+                to_flash.push(neighbour);
             }
         }
     }
-    log::debug!("energy_levels:\n{:#?}", energy_levels);
-    for pos in &flashed {
-        *energy_levels.map.get_mut(pos).unwrap() = 0
+    for level in energy_levels.levels.iter_mut() {
+        if *level >= FLASH {
+            *level = 0;
+        }
     }
-    log::debug!("energy_levels:\n{:#?}", energy_levels);
-    flashed.len()
+    log::trace!("energy_levels:\n{}", energy_levels);
+    flashes
 }
 
 pub fn flash_steps(energy_levels: &EnergyLevels, n: usize) -> usize {
-    let mut energy_levels = energy_levels.clone();
-    (0..n)
-        .map(|_| flash_step(&mut energy_levels))
-        .sum::<usize>()
+    energy_levels
+        .simulate()
+        .take(n)
+        .map(|frame| frame.flashes)
+        .sum()
+}
+
+/// The octopuses after a step
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub step: usize,
+    pub levels: EnergyLevels,
+    pub flashes: usize,
+}
+
+impl Frame {
+    /// Whether every octopus flashed during this step
+    pub fn synchronised(&self) -> bool {
+        self.flashes == self.levels.levels.len()
+    }
+}
+
+pub struct Simulation {
+    levels: EnergyLevels,
+    step: usize,
+}
+
+impl Iterator for Simulation {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let flashes = flash_step(&mut self.levels);
+        self.step += 1;
+        Some(Frame {
+            step: self.step,
+            levels: self.levels.clone(),
+            flashes,
+        })
+    }
+}
+
+/// The state after step `start` recurs every `period` steps
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+    /// The first step during which every octopus flashed, if one came before the cycle closed
+    pub first_sync: Option<usize>,
+}
+
+/// 256 colour terminal codes, from dark blue at 1 up to white for a flash
+const ANSI_COLOURS: [u8; 10] = [231, 17, 18, 19, 20, 21, 27, 33, 39, 45];
+
+impl EnergyLevels {
+    /// The grid as coloured blocks, two characters per octopus
+    pub fn render_ansi(&self) -> String {
+        let mut ansi = String::new();
+        for row in self.levels.chunks(self.width) {
+            for level in row {
+                ansi.push_str(&format!("\x1b[48;5;{}m  ", ANSI_COLOURS[*level as usize]));
+            }
+            ansi.push_str("\x1b[0m\n");
+        }
+        ansi
+    }
+}
+
+/// Frames for a terminal, each redrawing over the last from the top left corner
+pub fn ansi_frames<'a>(frames: impl IntoIterator<Item = &'a Frame>) -> Vec<String> {
+    frames
+        .into_iter()
+        .map(|frame| {
+            format!(
+                "\x1b[H{}step {:>4}: {:>3} flashes\x1b[K\n",
+                frame.levels.render_ansi(),
+                frame.step,
+                frame.flashes
+            )
+        })
+        .collect()
+}
+
+/// Palette for the GIF export, indexed by energy level and padded to 16 colours
+const GIF_PALETTE: [[u8; 3]; 16] = {
+    let mut palette = [[0; 3]; 16];
+    palette[0] = [255, 255, 255];
+    let mut level = 1;
+    while level < 10 {
+        palette[level] = [0, (level * 12) as u8, (40 + level * 20) as u8];
+        level += 1;
+    }
+    palette
+};
+
+/// An animated, looping GIF with each octopus as a `scale` pixel square and
+/// `delay` hundredths of a second between frames
+pub fn to_gif<'a>(
+    frames: impl IntoIterator<Item = &'a Frame>,
+    scale: usize,
+    delay: u16,
+) -> Vec<u8> {
+    let frames: Vec<&Frame> = frames.into_iter().collect();
+    let (width, height) = frames
+        .first()
+        .map_or((0, 0), |frame| frame.levels.dimensions());
+    let (px_width, px_height) = ((width * scale) as u16, (height * scale) as u16);
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(px_width.to_le_bytes());
+    gif.extend(px_height.to_le_bytes());
+    // Global colour table of 2^(3+1) colours
+    gif.extend([0b1111_0011, 0, 0]);
+    gif.extend(GIF_PALETTE.iter().flatten());
+    // Loop forever
+    gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+    for frame in frames {
+        gif.extend([0x21, 0xf9, 0x04, 0x00]);
+        gif.extend(delay.to_le_bytes());
+        gif.extend([0x00, 0x00]);
+        gif.push(0x2c);
+        gif.extend([0, 0, 0, 0]);
+        gif.extend(px_width.to_le_bytes());
+        gif.extend(px_height.to_le_bytes());
+        gif.push(0);
+        let pixels: Vec<u8> = frame
+            .levels
+            .levels
+            .chunks(width)
+            .flat_map(|row| {
+                let row: Vec<u8> = row.iter().flat_map(|&level| vec![level; scale]).collect();
+                row.repeat(scale)
+            })
+            .collect();
+        gif.extend(lzw_uncompressed(&pixels));
+    }
+    gif.push(0x3b);
+    gif
+}
+
+/// Image data for 4 bit pixels as LZW codes that never use the code table,
+/// clearing it before the code size would grow
+fn lzw_uncompressed(pixels: &[u8]) -> Vec<u8> {
+    const MIN_CODE_SIZE: u8 = 4;
+    const CLEAR: u32 = 1 << MIN_CODE_SIZE;
+    const END: u32 = CLEAR + 1;
+    const CODE_BITS: u32 = MIN_CODE_SIZE as u32 + 1;
+    const RUN: usize = 12;
+    let mut codes = vec![];
+    for run in pixels.chunks(RUN) {
+        codes.push(CLEAR);
+        codes.extend(run.iter().map(|&pixel| pixel as u32));
+    }
+    codes.push(END);
+    let mut data = vec![];
+    let (mut acc, mut bits) = (0u32, 0);
+    for code in codes {
+        acc |= code << bits;
+        bits += CODE_BITS;
+        while bits >= 8 {
+            data.push(acc as u8);
+            acc >>= 8;
+            bits -= 8;
+        }
+    }
+    if bits > 0 {
+        data.push(acc as u8);
+    }
+    let mut blocks = vec![MIN_CODE_SIZE];
+    for block in data.chunks(255) {
+        blocks.push(block.len() as u8);
+        blocks.extend(block);
+    }
+    blocks.push(0);
+    blocks
 }
 
 pub fn part1(energy_levels: &EnergyLevels) -> PartOutput<usize> {
-    let energy_levels = energy_levels.clone();
     PartOutput {
-        answer: flash_steps(&energy_levels, 100),
+        answer: flash_steps(energy_levels, 100),
     }
 }
 
 pub fn part2(energy_levels: &EnergyLevels) -> PartOutput<usize> {
-    let full_flash: usize = energy_levels.map.len();
-    let mut energy_levels = energy_levels.clone();
-    let mut step = 1;
-    loop {
-        let flashes = flash_step(&mut energy_levels);
-        log::debug!("{}=={}", flashes, full_flash);
-        if flashes == full_flash {
-            break;
-        }
-        step += 1;
-    }
+    let step = energy_levels
+        .simulate()
+        .find(Frame::synchronised)
+        .map(|frame| frame.step)
+        .unwrap();
     PartOutput { answer: step }
 }
 
@@ -150,6 +370,43 @@ mod tests {
         assert_eq!(flashes, 1656);
     }
 
+    #[test]
+    fn test_example_cycle() {
+        let energy_levels = parse(DAY.example).unwrap();
+        assert_eq!(
+            energy_levels.cycle(1000),
+            Some(Cycle {
+                start: 195,
+                period: 10,
+                first_sync: Some(195)
+            })
+        );
+        assert_eq!(energy_levels.cycle(100), None);
+    }
+
+    #[test]
+    fn test_example_export() {
+        let energy_levels = parse(DAY.example).unwrap();
+        let frames: Vec<Frame> = energy_levels.simulate().take(3).collect();
+        assert_eq!(frames[1].flashes, 35);
+        let gif = to_gif(&frames, 2, 10);
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..10], &[20, 0, 20, 0]);
+        assert_eq!(gif.last(), Some(&0x3b));
+        let ansi = ansi_frames(&frames);
+        assert_eq!(ansi.len(), 3);
+        assert!(ansi[1].starts_with("\x1b[H\x1b[48;5;"));
+        assert!(ansi[1].ends_with("step    2:  35 flashes\x1b[K\n"));
+    }
+
+    #[test]
+    fn test_parse_bad() {
+        assert!(matches!(parse("12\n3\n456"), Err(ParseError::Str(_))));
+        assert!(matches!(parse("12\n3x"), Err(ParseError::Str(_))));
+        assert!(matches!(parse(""), Err(ParseError::Empty)));
+        assert_eq!(parse("12\n34").unwrap().dimensions(), (2, 2));
+    }
+
     #[test]
     fn test_main() {
         let energy_levels = parse(&get_input(11)).unwrap();
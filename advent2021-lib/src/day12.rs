@@ -1,20 +1,39 @@
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use cached::{Cached, UnboundCache};
+
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
 #[derive(Clone, Debug)]
 pub struct Routes(HashMap<String, Vec<String>>);
 
+/// A route through the caves, printed as `start,A,b,end`
 #[derive(Clone, Debug)]
-struct Path<'a> {
+pub struct Path<'a> {
     caves: Vec<&'a str>,
     end: bool,
     small_caves: HashSet<&'a str>,
     small_cave_twice: bool,
 }
 
+impl std::fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.caves.join(","))
+    }
+}
+
 impl<'a> Path<'a> {
+    pub fn caves(&self) -> &[&'a str] {
+        &self.caves
+    }
+
+    /// Number of times the path passes through `cave`
+    pub fn visits(&self, cave: &str) -> usize {
+        self.caves.iter().filter(|c| **c == cave).count()
+    }
+
     fn new(caves: Vec<&'a str>) -> Self {
         Self {
             caves,
@@ -70,36 +89,208 @@ impl<'a> Path<'a> {
     }
 }
 
+/// Constraints on the paths to enumerate
+#[derive(Clone, Debug, Default)]
+pub struct PathFilter {
+    /// Only paths passing through this cave
+    pub through: Option<String>,
+    /// Only paths of at most this many caves, including start and end
+    pub max_length: Option<usize>,
+}
+
+impl PathFilter {
+    fn matches(&self, path: &Path) -> bool {
+        match &self.through {
+            Some(cave) => path.visits(cave) > 0,
+            None => true,
+        }
+    }
+}
+
+/// Lazy depth first enumeration of the paths from start to end
+pub struct Paths<'a> {
+    routes: &'a Routes,
+    allow_small_cave_twice: bool,
+    filter: PathFilter,
+    stack: Vec<Path<'a>>,
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = Path<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(path) = self.stack.pop() {
+            if path.end {
+                log::trace!("path {:?} is at end", path.caves);
+                if self.filter.matches(&path) {
+                    return Some(path);
+                }
+                continue;
+            }
+            if let Some(max_length) = self.filter.max_length {
+                if path.caves.len() >= max_length {
+                    continue;
+                }
+            }
+            let last = path.caves.last().unwrap();
+            log::trace!("path {:?}", path);
+            if let Some(options) = self.routes.0.get(*last) {
+                // Reversed, so that paths come out in the order the options are listed
+                self.stack.extend(
+                    options
+                        .iter()
+                        .rev()
+                        .filter_map(|next| path.add_next(next, self.allow_small_cave_twice)),
+                );
+            }
+        }
+        None
+    }
+}
+
 impl Routes {
-    fn path_steps<'a>(&'a self, path: Path<'a>, allow_small_cave_twice: bool) -> Vec<Path<'a>> {
-        if path.end {
-            log::trace!("path {:?} is at end", path.caves);
-            return vec![path];
-        }
-        let last = path.caves.last().unwrap();
-        log::trace!("path {:?}", path);
-        match self.0.get(last.to_owned()) {
-            None => {
-                if last == &"end" {
-                    panic!("Path State Inconsistent");
-                } else {
-                    panic!("Dead End Not Possible");
+    /// Iterate over all paths from start to end
+    pub fn paths(&self, allow_small_cave_twice: bool) -> Paths<'_> {
+        self.filtered_paths(allow_small_cave_twice, PathFilter::default())
+    }
+
+    /// Iterate over the paths from start to end satisfying the filter
+    pub fn filtered_paths(&self, allow_small_cave_twice: bool, filter: PathFilter) -> Paths<'_> {
+        Paths {
+            routes: self,
+            allow_small_cave_twice,
+            filter,
+            stack: vec![Path::new(vec!["start"])],
+        }
+    }
+
+    pub fn count_paths(&self, allow_small_cave_twice: bool) -> usize {
+        self.paths(allow_small_cave_twice).count()
+    }
+
+    /// Count the paths through a memoised search instead of enumerating them,
+    /// falling back to enumerating when there are too many caves to memoise
+    pub fn count_paths_memoised(&self, allow_small_cave_twice: bool) -> usize {
+        match CaveGraph::try_from(self) {
+            Ok(graph) => graph.count_paths(allow_small_cave_twice),
+            Err(_) => self.count_paths(allow_small_cave_twice),
+        }
+    }
+
+    /// Export the cave system as an undirected Graphviz graph,
+    /// small caves as plain ellipses and big caves as filled boxes
+    pub fn to_dot(&self) -> String {
+        let mut caves = BTreeSet::new();
+        let mut edges = BTreeSet::new();
+        for (from, tos) in &self.0 {
+            caves.insert(from.as_str());
+            for to in tos {
+                caves.insert(to.as_str());
+                edges.insert(if from < to { (from, to) } else { (to, from) });
+            }
+        }
+        let mut dot = "graph caves {\n".to_owned();
+        for cave in caves {
+            let style = if cave == "start" || cave == "end" {
+                "shape=doublecircle"
+            } else if cave == cave.to_lowercase() {
+                "shape=ellipse"
+            } else {
+                "shape=box, style=filled, fillcolor=lightgrey"
+            };
+            dot.push_str(&format!("    \"{}\" [{}];\n", cave, style));
+        }
+        for (a, b) in edges {
+            dot.push_str(&format!("    \"{}\" -- \"{}\";\n", a, b));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Cave system with the cave names interned to bit indices,
+/// to count paths without enumerating them
+#[derive(Clone, Debug)]
+pub struct CaveGraph {
+    start: usize,
+    end: usize,
+    /// Bitmask of the small caves
+    small: u64,
+    neighbours: Vec<Vec<usize>>,
+}
+
+/// Memoisation key: current cave, small caves visited, and whether a small cave was visited twice
+type CountKey = (usize, u64, bool);
+
+impl std::convert::TryFrom<&Routes> for CaveGraph {
+    type Error = ParseError;
+    fn try_from(routes: &Routes) -> Result<Self, Self::Error> {
+        let mut names: Vec<&str> = vec!["start", "end"];
+        for (from, tos) in &routes.0 {
+            for cave in std::iter::once(from).chain(tos) {
+                if !names.contains(&cave.as_str()) {
+                    names.push(cave);
                 }
             }
-            Some(options) => options
-                .iter()
-                .filter_map(|next| path.add_next(next, allow_small_cave_twice))
-                .map(|path| self.path_steps(path, allow_small_cave_twice))
-                .flatten()
-                .collect(),
         }
+        if names.len() > u64::BITS as usize {
+            return Err(ParseError::Str(format!("too many caves: {}", names.len())));
+        }
+        let index = |cave: &str| names.iter().position(|name| *name == cave).unwrap();
+        let mut neighbours = vec![Vec::new(); names.len()];
+        for (from, tos) in &routes.0 {
+            neighbours[index(from)] = tos.iter().map(|to| index(to)).collect();
+        }
+        let small = names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| **name == name.to_lowercase())
+            .fold(0, |small, (idx, _)| small | 1 << idx);
+        Ok(Self {
+            start: 0,
+            end: 1,
+            small,
+            neighbours,
+        })
     }
+}
 
-    fn count_paths(&self, allow_small_cave_twice: bool) -> usize {
-        let path = Path::new(vec!["start"]);
-        let paths = self.path_steps(path, allow_small_cave_twice);
-        log::debug!("paths: {:?}", paths);
-        paths.len()
+impl CaveGraph {
+    pub fn count_paths(&self, allow_small_cave_twice: bool) -> usize {
+        let mut cache = UnboundCache::new();
+        self.count_from(
+            &mut cache,
+            (self.start, 1 << self.start, !allow_small_cave_twice),
+        )
+    }
+
+    /// Number of ways to reach the end from the state in `key`
+    fn count_from(&self, cache: &mut UnboundCache<CountKey, usize>, key: CountKey) -> usize {
+        let (cave, visited, small_cave_twice) = key;
+        if cave == self.end {
+            return 1;
+        }
+        if let Some(count) = cache.cache_get(&key) {
+            return *count;
+        }
+        let count = self.neighbours[cave]
+            .iter()
+            .map(|next| {
+                let bit = 1 << next;
+                if visited & bit == 0 {
+                    self.count_from(
+                        cache,
+                        (*next, visited | (bit & self.small), small_cave_twice),
+                    )
+                } else if !small_cave_twice {
+                    self.count_from(cache, (*next, visited, true))
+                } else {
+                    0
+                }
+            })
+            .sum();
+        cache.cache_set(key, count);
+        count
     }
 }
 
@@ -124,13 +315,13 @@ pub fn parse(input: &str) -> ParseResult<Routes> {
 
 pub fn part1(routes: &Routes) -> PartOutput<usize> {
     PartOutput {
-        answer: routes.count_paths(false),
+        answer: routes.count_paths_memoised(false),
     }
 }
 
 pub fn part2(routes: &Routes) -> PartOutput<usize> {
     PartOutput {
-        answer: routes.count_paths(true),
+        answer: routes.count_paths_memoised(true),
     }
 }
 
@@ -193,10 +384,133 @@ mod tests {
         assert_eq!(routes.count_paths(true), 3509);
     }
 
+    #[test]
+    fn test_example1_paths() {
+        let routes = parse("start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end").unwrap();
+        let paths: BTreeSet<String> = routes.paths(false).map(|path| path.to_string()).collect();
+        let expected: BTreeSet<String> = [
+            "start,A,b,A,c,A,end",
+            "start,A,b,A,end",
+            "start,A,b,end",
+            "start,A,c,A,b,A,end",
+            "start,A,c,A,b,end",
+            "start,A,c,A,end",
+            "start,A,end",
+            "start,b,A,c,A,end",
+            "start,b,A,end",
+            "start,b,end",
+        ]
+        .iter()
+        .map(|path| path.to_string())
+        .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn test_example1_filtered_paths() {
+        let routes = parse("start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end").unwrap();
+        let through_c = PathFilter {
+            through: Some("c".to_owned()),
+            ..PathFilter::default()
+        };
+        assert_eq!(routes.filtered_paths(false, through_c.clone()).count(), 5);
+        let short = PathFilter {
+            max_length: Some(4),
+            ..PathFilter::default()
+        };
+        let paths: Vec<String> = routes
+            .filtered_paths(false, short)
+            .map(|path| path.to_string())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "start,A,b,end",
+                "start,A,end",
+                "start,b,A,end",
+                "start,b,end"
+            ]
+        );
+        let both = PathFilter {
+            max_length: Some(6),
+            ..through_c
+        };
+        assert!(routes
+            .filtered_paths(true, both)
+            .all(|path| path.visits("c") > 0 && path.caves().len() <= 6));
+        let path = routes
+            .paths(true)
+            .find(|path| path.visits("b") == 2)
+            .unwrap();
+        assert_eq!(path.caves().first(), Some(&"start"));
+        assert_eq!(path.caves().last(), Some(&"end"));
+    }
+
+    #[test]
+    fn test_example1_dot() {
+        let routes = parse("start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end").unwrap();
+        let dot = routes.to_dot();
+        assert!(dot.starts_with("graph caves {\n"));
+        assert!(dot.contains("\"A\" [shape=box, style=filled, fillcolor=lightgrey];"));
+        assert!(dot.contains("\"c\" [shape=ellipse];"));
+        assert!(dot.contains("\"start\" [shape=doublecircle];"));
+        assert!(dot.contains("\"A\" -- \"start\";"));
+        assert_eq!(dot.matches(" -- ").count(), 7);
+    }
+
+    #[test]
+    fn test_memoised_agrees() {
+        for input in [
+            "start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end",
+            "dc-end\nHN-start\nstart-kj\ndc-start\ndc-HN\nLN-dc\nHN-end\nkj-sa\nkj-HN\nkj-dc",
+            DAY.example,
+        ] {
+            let routes = parse(input).unwrap();
+            for allow_small_cave_twice in [false, true] {
+                assert_eq!(
+                    routes.count_paths_memoised(allow_small_cave_twice),
+                    routes.count_paths(allow_small_cave_twice)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_memoised_dense() {
+        // Every small cave connected to every other and to a big cave
+        let caves = ["a", "b", "c", "d", "e"];
+        let mut lines = vec!["start-X".to_owned(), "X-end".to_owned()];
+        for (idx, cave) in caves.iter().enumerate() {
+            lines.push(format!("X-{}", cave));
+            for other in &caves[idx + 1..] {
+                lines.push(format!("{}-{}", cave, other));
+            }
+        }
+        let routes = parse(&lines.join("\n")).unwrap();
+        assert_eq!(
+            routes.count_paths_memoised(false),
+            routes.count_paths(false)
+        );
+        assert_eq!(routes.count_paths_memoised(true), routes.count_paths(true));
+    }
+
+    #[test]
+    fn test_too_many_caves() {
+        let input: Vec<String> = (0..70).map(|idx| format!("start-c{}", idx)).collect();
+        let routes = parse(&input.join("\n")).unwrap();
+        assert!(CaveGraph::try_from(&routes).is_err());
+        assert_eq!(part1(&routes).answer, 0);
+        let input = format!("{}\nc0-end", input.join("\n"));
+        let routes = parse(&input).unwrap();
+        assert_eq!(routes.count_paths_memoised(false), 1);
+    }
+
     #[test]
     fn test_main() {
         let routes = parse(&get_input(12)).unwrap();
         assert_eq!(part1(&routes).answer.to_string(), "5958");
         assert_eq!(part2(&routes).answer.to_string(), "150426");
+        assert_eq!(routes.count_paths(false), 5958);
+        assert_eq!(routes.count_paths(true), 150426);
     }
 }
@@ -3,20 +3,30 @@ use std::collections::HashSet;
 use recap::Recap;
 use serde::Deserialize;
 
-use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
+use crate::{ocr, Day, DayCalc, ParseError, ParseResult, PartOutput};
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
-enum FoldDirection {
+pub enum FoldDirection {
     X,
     Y,
 }
 
-#[derive(Debug, Deserialize, Recap)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Recap)]
 #[recap(regex = r#"^fold along (?P<direction>.+)=(?P<location>.+)$"#)]
 pub struct Fold {
-    direction: FoldDirection,
-    location: usize,
+    pub direction: FoldDirection,
+    pub location: usize,
+}
+
+impl std::fmt::Display for Fold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let axis = match self.direction {
+            FoldDirection::X => 'x',
+            FoldDirection::Y => 'y',
+        };
+        write!(f, "fold along {}={}", axis, self.location)
+    }
 }
 
 #[derive(Clone)]
@@ -30,19 +40,7 @@ pub struct TransparentPaper {
 
 impl std::fmt::Debug for Dots {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let max_x = self.0.iter().max_by_key(|pos| pos.0).unwrap().0;
-        let max_y = self.0.iter().max_by_key(|pos| pos.1).unwrap().1;
-        for y in 0..=max_y {
-            for x in 0..=max_x {
-                if self.0.contains(&(x, y)) {
-                    write!(f, "#")?;
-                } else {
-                    write!(f, ".")?;
-                }
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        writeln!(f, "{}", ocr::render(&self.0))
     }
 }
 
@@ -73,74 +71,246 @@ impl std::str::FromStr for TransparentPaper {
     }
 }
 
+/// Instructions whose folds can all be made, checked by making them
 pub fn parse(input: &str) -> ParseResult<TransparentPaper> {
-    input.parse()
-}
-
-pub fn apply_fold(dots: &mut Dots, fold: &Fold) {
-    log::debug!("applying {:?} on dots:\n{:#?}", fold, dots);
-    match fold.direction {
-        FoldDirection::X => {
-            let dots_to_fold: Vec<(usize, usize)> = dots
-                .0
-                .iter()
-                .filter(|dot| dot.0 > fold.location)
-                .cloned()
-                .collect();
-            for dot_to_fold in dots_to_fold {
-                dots.0.remove(&dot_to_fold);
-                dots.0.insert((
-                    fold.location - (dot_to_fold.0 - fold.location),
-                    dot_to_fold.1,
-                ));
+    let paper: TransparentPaper = input.parse()?;
+    apply_folds(&paper).map_err(|e| ParseError::Str(e.to_string()))?;
+    Ok(paper)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FoldError {
+    /// The fold line is past the edge of the paper
+    OutsidePaper { fold: Fold, size: usize },
+    /// The part folded over is longer than the rest, so it would hang off the edge
+    OffCentre { fold: Fold, size: usize },
+    /// A dot lies on the fold line
+    DotOnFold { fold: Fold, dot: (usize, usize) },
+}
+
+impl std::fmt::Display for FoldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutsidePaper { fold, size } => {
+                write!(f, "cannot {}, the paper is only {} long", fold, size)
+            }
+            Self::OffCentre { fold, size } => write!(
+                f,
+                "cannot {}, the paper is {} long so the folded part would overhang",
+                fold, size
+            ),
+            Self::DotOnFold { fold, dot } => {
+                write!(f, "cannot {}, there is a dot at {:?}", fold, dot)
             }
         }
-        FoldDirection::Y => {
-            let dots_to_fold: Vec<(usize, usize)> = dots
-                .0
-                .iter()
-                .filter(|dot| dot.1 > fold.location)
-                .cloned()
-                .collect();
-            for dot_to_fold in dots_to_fold {
-                dots.0.remove(&dot_to_fold);
-                dots.0.insert((
-                    dot_to_fold.0,
-                    fold.location - (dot_to_fold.1 - fold.location),
-                ));
+    }
+}
+
+/// A sheet of transparent paper and the dots on it
+#[derive(Clone, Debug)]
+pub struct Paper {
+    dots: Dots,
+    width: usize,
+    height: usize,
+}
+
+impl Paper {
+    /// The smallest sheet that has all of the dots on it
+    pub fn new(dots: Dots) -> Self {
+        let width = dots.0.iter().map(|dot| dot.0 + 1).max().unwrap_or(0);
+        let height = dots.0.iter().map(|dot| dot.1 + 1).max().unwrap_or(0);
+        Self {
+            dots,
+            width,
+            height,
+        }
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn dots(&self) -> &HashSet<(usize, usize)> {
+        &self.dots.0
+    }
+
+    /// Folds the bottom or right part of the paper up or left along the line
+    pub fn fold(&self, fold: &Fold) -> Result<Paper, FoldError> {
+        let size = match fold.direction {
+            FoldDirection::X => self.width,
+            FoldDirection::Y => self.height,
+        };
+        if fold.location >= size {
+            return Err(FoldError::OutsidePaper {
+                fold: fold.clone(),
+                size,
+            });
+        }
+        if size - fold.location - 1 > fold.location {
+            return Err(FoldError::OffCentre {
+                fold: fold.clone(),
+                size,
+            });
+        }
+        let mirror = |pos: usize| {
+            if pos > fold.location {
+                2 * fold.location - pos
+            } else {
+                pos
             }
+        };
+        let along = |&(x, y): &(usize, usize)| match fold.direction {
+            FoldDirection::X => x,
+            FoldDirection::Y => y,
+        };
+        if let Some(dot) = self
+            .dots
+            .0
+            .iter()
+            .filter(|dot| along(dot) == fold.location)
+            .min()
+        {
+            return Err(FoldError::DotOnFold {
+                fold: fold.clone(),
+                dot: *dot,
+            });
         }
+        let dots = self
+            .dots
+            .0
+            .iter()
+            .map(|&(x, y)| match fold.direction {
+                FoldDirection::X => (mirror(x), y),
+                FoldDirection::Y => (x, mirror(y)),
+            })
+            .collect();
+        let (width, height) = match fold.direction {
+            FoldDirection::X => (fold.location, self.height),
+            FoldDirection::Y => (self.width, fold.location),
+        };
+        Ok(Paper {
+            dots: Dots(dots),
+            width,
+            height,
+        })
+    }
+
+    /// The paper after each of the folds in turn, stopping at the first that fails
+    pub fn fold_steps<'a, I>(&self, folds: I) -> impl Iterator<Item = Result<Paper, FoldError>> + 'a
+    where
+        I: IntoIterator<Item = &'a Fold>,
+        I::IntoIter: 'a,
+    {
+        let mut paper = Some(self.clone());
+        folds.into_iter().map_while(move |fold| {
+            let folded = paper.take()?.fold(fold);
+            if let Ok(folded) = &folded {
+                paper = Some(folded.clone());
+            }
+            Some(folded)
+        })
+    }
+
+    /// The whole sheet, with `#` for dots
+    pub fn render_ascii(&self) -> String {
+        (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        if self.dots.0.contains(&(x, y)) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    /// The sheet scaled to `width` pixels, with the next fold line dashed
+    pub fn render_svg(&self, width: usize, next_fold: Option<&Fold>) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" viewBox=\"-1 -1 {} {}\">\n",
+            width,
+            self.width + 2,
+            self.height + 2
+        );
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"#eef\" stroke=\"#99a\" stroke-width=\"0.2\"/>\n",
+            self.width, self.height
+        ));
+        let mut dots: Vec<&(usize, usize)> = self.dots.0.iter().collect();
+        dots.sort_unstable();
+        for (x, y) in dots {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" fill=\"#224\"/>\n",
+                x, y
+            ));
+        }
+        if let Some(fold) = next_fold {
+            let middle = fold.location as f64 + 0.5;
+            let (x1, y1, x2, y2) = match fold.direction {
+                FoldDirection::X => (middle, 0.0, middle, self.height as f64),
+                FoldDirection::Y => (0.0, middle, self.width as f64, middle),
+            };
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"red\" stroke-width=\"0.4\" stroke-dasharray=\"1 1\"/>\n",
+                x1, y1, x2, y2
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
     }
 }
 
-pub fn apply_folds(paper: &TransparentPaper) -> Dots {
-    let mut dots = paper.dots.clone();
-    for fold in &paper.folds {
-        apply_fold(&mut dots, fold);
+impl TransparentPaper {
+    pub fn paper(&self) -> Paper {
+        Paper::new(self.dots.clone())
+    }
+
+    pub fn folds(&self) -> &[Fold] {
+        &self.folds
     }
-    dots
+
+    /// The paper after each fold, in the order given by the instructions
+    pub fn fold_steps(&self) -> impl Iterator<Item = Result<Paper, FoldError>> + '_ {
+        self.paper().fold_steps(&self.folds)
+    }
+}
+
+pub fn apply_folds(paper: &TransparentPaper) -> Result<Paper, FoldError> {
+    paper
+        .fold_steps()
+        .last()
+        .unwrap_or_else(|| Ok(paper.paper()))
 }
 
 // TODO, allow returning a usize
 pub fn part1(paper: &TransparentPaper) -> PartOutput<String> {
-    let mut dots = paper.dots.clone();
-    let fold = paper.folds.first().unwrap();
-    apply_fold(&mut dots, fold);
+    let visible = match paper.fold_steps().next() {
+        Some(folded) => folded.expect("parsing checks the folds").dots().len(),
+        None => paper.dots.0.len(),
+    };
     PartOutput {
-        answer: dots.0.len().to_string(),
+        answer: visible.to_string(),
     }
 }
 
 pub fn part2(paper: &TransparentPaper) -> PartOutput<String> {
-    let dots = apply_folds(paper);
+    let folded = apply_folds(paper).expect("parsing checks the folds");
     PartOutput {
-        answer: format!("The code is\n{:#?}", dots),
+        answer: ocr::read(folded.dots()),
     }
 }
 
 pub const DAY: Day<TransparentPaper, String> = Day {
     title: "Transparent Origami",
-    display: ("{answer} dots are visible after one fold.", "{answer}"),
+    display: (
+        "{answer} dots are visible after one fold.",
+        "The code is {answer}",
+    ),
     calc: DayCalc {
         parse,
         part1,
@@ -158,15 +328,79 @@ mod tests {
     #[test]
     fn test_example_part1() {
         let paper = parse(DAY.example).unwrap();
-        let mut dots = paper.dots.clone();
         let fold = paper.folds.first().unwrap();
-        apply_fold(&mut dots, fold);
-        assert_eq!(dots.0.len(), 17);
+        let folded = paper.paper().fold(fold).unwrap();
+        assert_eq!(folded.dots().len(), 17);
+    }
+
+    #[test]
+    fn test_example_steps() {
+        let paper = parse(DAY.example).unwrap();
+        assert_eq!(paper.paper().dimensions(), (11, 15));
+        let steps: Vec<Paper> = paper.fold_steps().collect::<Result<_, _>>().unwrap();
+        let dimensions: Vec<(usize, usize)> = steps.iter().map(Paper::dimensions).collect();
+        assert_eq!(dimensions, vec![(11, 7), (5, 7)]);
+        assert_eq!(
+            steps[1].render_ascii(),
+            "#####\n#...#\n#...#\n#...#\n#####\n.....\n.....\n"
+        );
+        // Folding the other way round ends up with the same square
+        let reversed: Vec<Fold> = paper.folds().iter().rev().cloned().collect();
+        let last = paper.paper().fold_steps(&reversed).last().unwrap().unwrap();
+        assert_eq!(last.dimensions(), (5, 7));
+        assert_eq!(last.dots(), steps[1].dots());
+    }
+
+    #[test]
+    fn test_example_fold_errors() {
+        let paper = parse(DAY.example).unwrap().paper();
+        let fold = |location| Fold {
+            direction: FoldDirection::Y,
+            location,
+        };
+        assert_eq!(
+            paper.fold(&fold(3)).unwrap_err(),
+            FoldError::OffCentre {
+                fold: fold(3),
+                size: 15
+            }
+        );
+        assert_eq!(
+            paper.fold(&fold(20)).unwrap_err(),
+            FoldError::OutsidePaper {
+                fold: fold(20),
+                size: 15
+            }
+        );
+        assert_eq!(
+            paper.fold(&fold(10)).unwrap_err(),
+            FoldError::DotOnFold {
+                fold: fold(10),
+                dot: (1, 10)
+            }
+        );
+        assert_eq!(paper.fold(&fold(9)).unwrap().dimensions(), (11, 9));
+        let steps: Vec<_> = paper.fold_steps(&[fold(10), fold(4)]).collect();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(fold(10).to_string(), "fold along y=10");
+        // Each step can still be shown, but the puzzle needs every fold to work
+        let input = format!("{}\nfold along x=9", DAY.example.trim_end());
+        let instructions: TransparentPaper = input.parse().unwrap();
+        assert_eq!(instructions.fold_steps().filter(Result::is_ok).count(), 2);
+        assert!(matches!(parse(&input), Err(ParseError::Str(_))));
+    }
+
+    #[test]
+    fn test_example_part2() {
+        let paper = parse(DAY.example).unwrap();
+        // The example folds into a square, which isn't a letter
+        assert_eq!(part2(&paper).answer, "\n#####\n#...#\n#...#\n#...#\n#####");
     }
 
     #[test]
     fn test_main() {
         let something = parse(&get_input(13)).unwrap();
         assert_eq!(part1(&something).answer.to_string(), "814");
+        assert_eq!(part2(&something).answer.to_string(), "PZEHRAER");
     }
 }
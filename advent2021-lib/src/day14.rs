@@ -1,81 +1,74 @@
-use ndarray::prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use crate::matrix::{Checked, Count, Matrix};
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
-#[derive(Clone, PartialEq, Eq, Hash)]
-pub struct Element(u8);
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Element(pub char);
 
-impl From<&Element> for usize {
-    fn from(element: &Element) -> Self {
-        element.0.into()
-    }
-}
-
-impl From<&Element> for char {
-    fn from(element: &Element) -> Self {
-        char::from_u32(u32::from('A') + u32::from(element.0)).unwrap()
+impl From<Element> for char {
+    fn from(element: Element) -> Self {
+        element.0
     }
 }
 
 impl std::fmt::Debug for Element {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", char::from(self))
-    }
-}
-
-impl Element {
-    fn from_char(c: char) -> Self {
-        let offset = u32::from(c) - u32::from('A');
-        Self(offset.try_into().unwrap())
+        write!(f, "{}", self.0)
     }
 }
 
+/// The template and pair insertion rules. Elements can be any characters,
+/// and pairs without a rule are left as they are.
 #[derive(Debug)]
 pub struct PolymerManual {
     template: Vec<Element>,
-    pair_insertion: Array2<usize>,
+    rules: HashMap<(Element, Element), Element>,
+    /// Every element in the template or rules, in order
+    alphabet: Vec<Element>,
 }
 
-const DIM: usize = ('Z' as usize - 'A' as usize) + 1;
-
 impl std::str::FromStr for PolymerManual {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let (template_str, pair_insertion_rules_str) = s
             .split_once("\n\n")
             .ok_or_else(|| ParseError::Str(s.to_owned()))?;
-        let template = template_str.chars().map(Element::from_char).collect();
-        let pair_insertion_rules = pair_insertion_rules_str
-            .lines()
-            .map(|line| {
-                let (left, right) = line
-                    .split_once(" -> ")
-                    .ok_or_else(|| ParseError::Str(line.to_owned()))?;
-                assert_eq!(right.len(), 1);
-                Ok((
-                    (
-                        Element::from_char(left.chars().next().unwrap()),
-                        Element::from_char(left.chars().last().unwrap()),
-                    ),
-                    Element::from_char(right.chars().next().unwrap()),
-                ))
-            })
-            .collect::<ParseResult<HashMap<(Element, Element), Element>>>()?;
-        let pair_insertion = {
-            let mut pair_insertion = Array2::zeros((DIM * DIM, DIM * DIM));
-            for ((from_left, from_right), insert) in pair_insertion_rules.iter() {
-                let from: usize = DIM * usize::from(from_left) + usize::from(from_right);
-                let to_left: usize = DIM * usize::from(from_left) + usize::from(insert);
-                let to_right: usize = DIM * usize::from(insert) + usize::from(from_right);
-                pair_insertion[[to_left, from]] = 1;
-                pair_insertion[[to_right, from]] = 1;
+        let template: Vec<Element> = template_str.trim().chars().map(Element).collect();
+        if template.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut rules = HashMap::new();
+        for line in pair_insertion_rules_str.lines() {
+            let invalid = || ParseError::Str(line.to_owned());
+            let (left, right) = line.trim().split_once(" -> ").ok_or_else(invalid)?;
+            let left: Vec<char> = left.chars().collect();
+            let right: Vec<char> = right.chars().collect();
+            let (pair, insert) = match (&left[..], &right[..]) {
+                (&[first, second], &[insert]) => {
+                    ((Element(first), Element(second)), Element(insert))
+                }
+                _ => return Err(invalid()),
+            };
+            if matches!(rules.insert(pair, insert), Some(other) if other != insert) {
+                return Err(invalid());
             }
-            pair_insertion
-        };
+        }
+        let mut alphabet: Vec<Element> = template
+            .iter()
+            .copied()
+            .chain(
+                rules
+                    .iter()
+                    .flat_map(|(&(first, second), &insert)| [first, second, insert]),
+            )
+            .collect();
+        alphabet.sort_unstable();
+        alphabet.dedup();
         Ok(Self {
             template,
-            pair_insertion,
+            rules,
+            alphabet,
         })
     }
 }
@@ -84,116 +77,142 @@ pub fn parse(input: &str) -> ParseResult<PolymerManual> {
     input.parse()
 }
 
-fn step(
-    pairs: &Array2<usize>,
-    counts: Array2<usize>,
-    first_last: (&Element, &Element),
-) -> Array2<usize> {
-    log::debug!("step: {:?}", array_to_counts(&counts, first_last));
-    pairs.dot(&counts)
+/// The element counts overflowed a `u64`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overflow {
+    pub steps: u64,
 }
 
-fn steps(
-    pairs: &Array2<usize>,
-    counts: Array2<usize>,
-    n: usize,
-    first_last: (&Element, &Element),
-) -> Array2<usize> {
-    (0..n).fold(counts, |acc, _| step(pairs, acc, first_last))
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "element counts overflow after {} steps", self.steps)
+    }
 }
 
-fn array_to_counts(
-    arr_counts: &Array2<usize>,
-    first_last: (&Element, &Element),
-) -> HashMap<Element, usize> {
-    let mut counts = HashMap::new();
-    for row in 0..arr_counts.nrows() {
-        let additional: usize = arr_counts[[row, 0]];
-        if additional > 0 {
-            let left: u8 = (row / DIM).try_into().unwrap();
-            let right: u8 = (row % DIM).try_into().unwrap();
-            counts
-                .entry(Element(left))
-                .and_modify(|count| *count += additional)
-                .or_insert(additional);
-            counts
-                .entry(Element(right))
-                .and_modify(|count| *count += additional)
-                .or_insert(additional);
-        }
+impl PolymerManual {
+    pub fn template(&self) -> &[Element] {
+        &self.template
+    }
+
+    pub fn alphabet(&self) -> &[Element] {
+        &self.alphabet
     }
-    log::trace!("counts: {:?}", counts);
-    for (element, count) in counts.iter_mut() {
-        log::trace!("{:?}, {:?}, {:?}", element, count, first_last);
-        if first_last.0 == element {
-            *count += 1
+
+    fn element_index(&self, element: Element) -> usize {
+        self.alphabet.binary_search(&element).unwrap()
+    }
+
+    fn pair_index(&self, left: Element, right: Element) -> usize {
+        self.alphabet.len() * self.element_index(left) + self.element_index(right)
+    }
+
+    /// Maps the counts of each pair of elements to the counts a step later
+    pub fn transition<T: Count>(&self) -> Matrix<T> {
+        let mut matrix: Matrix<T> = Matrix::zeros(self.alphabet.len() * self.alphabet.len());
+        for &left in &self.alphabet {
+            for &right in &self.alphabet {
+                let from = self.pair_index(left, right);
+                let to = match self.rules.get(&(left, right)) {
+                    Some(&insert) => vec![(left, insert), (insert, right)],
+                    None => vec![(left, right)],
+                };
+                for (to_left, to_right) in to {
+                    let to = self.pair_index(to_left, to_right);
+                    matrix[(to, from)] = matrix[(to, from)].clone() + T::one();
+                }
+            }
         }
-        if first_last.1 == element {
-            *count += 1
+        matrix
+    }
+
+    fn template_pairs<T: Count>(&self) -> Vec<T> {
+        let mut pairs = vec![T::zero(); self.alphabet.len() * self.alphabet.len()];
+        for window in self.template.windows(2) {
+            let idx = self.pair_index(window[0], window[1]);
+            pairs[idx] = pairs[idx].clone() + T::one();
         }
-        *count /= 2;
+        pairs
+    }
+
+    /// Every element is the left of a pair except the last, which never changes
+    fn element_counts<T: Count>(&self, pairs: &[T]) -> BTreeMap<Element, T> {
+        let size = self.alphabet.len();
+        let last = *self.template.last().unwrap();
+        self.alphabet
+            .iter()
+            .enumerate()
+            .map(|(idx, &element)| {
+                let count = pairs[idx * size..(idx + 1) * size]
+                    .iter()
+                    .fold(T::zero(), |acc, count| acc + count.clone());
+                let count = if element == last {
+                    count + T::one()
+                } else {
+                    count
+                };
+                (element, count)
+            })
+            .filter(|(_, count)| !count.is_zero())
+            .collect()
+    }
+
+    /// How many of each element are in the polymer after `steps`, in
+    /// O(log steps) matrix multiplications
+    ///
+    /// ```
+    /// let manual = advent2021_lib::day14::parse("NN\n\nNN -> N\n").unwrap();
+    /// let counts = manual.counts::<u64>(3);
+    /// assert_eq!(counts.values().sum::<u64>(), 9);
+    /// ```
+    pub fn counts<T: Count>(&self, steps: u64) -> BTreeMap<Element, T> {
+        let pairs = self.transition().pow(steps).apply(&self.template_pairs());
+        self.element_counts(&pairs)
     }
-    log::trace!("counts: {:?}", counts);
-    counts
-}
 
-fn template_to_array(template: &[Element]) -> Array2<usize> {
-    let count_indices: Vec<usize> = template
-        .windows(2)
-        .map(|window| {
-            DIM * usize::from(window.first().unwrap()) + usize::from(window.last().unwrap())
+    /// The counts after `steps`, or an error if any of them won't fit in a `u64`
+    pub fn checked_counts(&self, steps: u64) -> Result<BTreeMap<Element, u64>, Overflow> {
+        self.counts::<Checked>(steps)
+            .into_iter()
+            .map(|(element, count)| Ok((element, count.0.ok_or(Overflow { steps })?)))
+            .collect()
+    }
+
+    /// The counts before any insertions, then after each step in turn
+    pub fn count_steps<'a, T: Count + 'a>(
+        &'a self,
+    ) -> impl Iterator<Item = BTreeMap<Element, T>> + 'a {
+        let transition = self.transition();
+        std::iter::successors(Some(self.template_pairs()), move |pairs: &Vec<T>| {
+            Some(transition.apply(pairs))
         })
-        .collect();
-    let mut arr_counts = Array2::zeros((DIM * DIM, 1));
-    for count_idx in count_indices {
-        arr_counts[[count_idx, 0]] += 1;
+        .map(move |pairs| self.element_counts(&pairs))
     }
-    arr_counts
 }
 
-pub fn get_counts(
-    pair_insertion: &Array2<usize>,
-    template: Vec<Element>,
-    n: usize,
-    first_last: (&Element, &Element),
-) -> HashMap<Element, usize> {
-    let arr_counts = template_to_array(&template);
-    log::debug!("init: {:?}", array_to_counts(&arr_counts, first_last));
-    let arr_counts = steps(pair_insertion, arr_counts, n, first_last);
-    array_to_counts(&arr_counts, first_last)
+/// The count of the most common element minus that of the least common
+pub fn spread<T: Clone + Ord + std::ops::Sub<Output = T>>(
+    counts: &BTreeMap<Element, T>,
+) -> Option<T> {
+    let max = counts.values().max()?.clone();
+    let min = counts.values().min()?.clone();
+    Some(max - min)
 }
 
-pub fn part1(manual: &PolymerManual) -> PartOutput<usize> {
-    let first_last = (
-        manual.template.first().unwrap(),
-        manual.template.last().unwrap(),
-    );
-    let counts = get_counts(
-        &manual.pair_insertion,
-        manual.template.clone(),
-        10,
-        first_last,
-    );
+fn most_minus_least(manual: &PolymerManual, steps: u64) -> usize {
+    let counts = manual.checked_counts(steps).unwrap();
     log::debug!("final: {:?}", counts);
+    spread(&counts).unwrap().try_into().unwrap()
+}
+
+pub fn part1(manual: &PolymerManual) -> PartOutput<usize> {
     PartOutput {
-        answer: counts.values().max().unwrap() - counts.values().min().unwrap(),
+        answer: most_minus_least(manual, 10),
     }
 }
 
 pub fn part2(manual: &PolymerManual) -> PartOutput<usize> {
-    let first_last = (
-        manual.template.first().unwrap(),
-        manual.template.last().unwrap(),
-    );
-    let counts = get_counts(
-        &manual.pair_insertion,
-        manual.template.clone(),
-        40,
-        first_last,
-    );
-    log::debug!("final: {:?}", counts);
     PartOutput {
-        answer: counts.values().max().unwrap() - counts.values().min().unwrap(),
+        answer: most_minus_least(manual, 40),
     }
 }
 
@@ -215,72 +234,89 @@ pub const DAY: Day<PolymerManual, usize> = Day {
 mod tests {
     use super::*;
     use crate::get_input;
+    use num_bigint::BigUint;
     use test_log::test;
 
+    fn counts_of(polymer: &str) -> BTreeMap<Element, u64> {
+        let mut counts = BTreeMap::new();
+        for c in polymer.chars() {
+            *counts.entry(Element(c)).or_default() += 1;
+        }
+        counts
+    }
+
     #[test]
     fn test_template() {
-        let template = vec![
-            Element::from_char('N'),
-            Element::from_char('N'),
-            Element::from_char('C'),
-            Element::from_char('B'),
-        ];
-        let first_last = (template.first().unwrap(), template.last().unwrap());
-        let counts = array_to_counts(&template_to_array(&template), first_last);
-        assert_eq!(counts.get(&Element::from_char('N')).unwrap(), &2);
+        let manual = parse("NNCB\n\nCH -> B\n").unwrap();
+        let counts = manual.counts::<u64>(0);
+        assert_eq!(counts.get(&Element('N')).unwrap(), &2);
     }
 
     #[test]
     fn test_template_ends() {
-        let template = vec![
-            Element::from_char('N'),
-            Element::from_char('N'),
-            Element::from_char('C'),
-            Element::from_char('N'),
-        ];
-        let first_last = (template.first().unwrap(), template.last().unwrap());
-        let counts = array_to_counts(&template_to_array(&template), first_last);
-        assert_eq!(counts.get(&Element::from_char('N')).unwrap(), &3);
+        let manual = parse("NNCN\n\nCH -> B\n").unwrap();
+        let counts = manual.counts::<u64>(0);
+        assert_eq!(counts.get(&Element('N')).unwrap(), &3);
     }
 
     #[test]
-    fn test_example_part1() {
+    fn test_example_steps() {
         let manual = parse(DAY.example).unwrap();
-        let first_last = (
-            manual.template.first().unwrap(),
-            manual.template.last().unwrap(),
-        );
-        log::trace!("manual: {:?}", manual);
-        let counts = get_counts(
-            &manual.pair_insertion,
-            manual.template.clone(),
-            10,
-            first_last,
+        let steps: Vec<BTreeMap<Element, u64>> = manual.count_steps().take(5).collect();
+        assert_eq!(steps[0], counts_of("NNCB"));
+        assert_eq!(steps[1], counts_of("NCNBCHB"));
+        assert_eq!(steps[2], counts_of("NBCCNBBBCBHCB"));
+        assert_eq!(
+            steps[4],
+            counts_of("NBBNBNBBCCNBCNCCNBBNBBNBBBNBBNBBCBHCBHHNHCBBCBHCB")
         );
+        assert_eq!(manual.counts::<u64>(4), steps[4]);
+    }
+
+    #[test]
+    fn test_example_part1() {
+        let manual = parse(DAY.example).unwrap();
+        let counts = manual.checked_counts(10).unwrap();
         log::debug!("counts: {:?}", counts);
-        assert_eq!(counts.get(&Element::from_char('B')).unwrap(), &1749);
+        assert_eq!(counts.get(&Element('B')).unwrap(), &1749);
+        assert_eq!(spread(&counts), Some(1588));
     }
 
     #[test]
     fn test_example_part2() {
         let manual = parse(DAY.example).unwrap();
-        let first_last = (
-            manual.template.first().unwrap(),
-            manual.template.last().unwrap(),
-        );
-        log::trace!("manual: {:?}", manual);
-        let counts = get_counts(
-            &manual.pair_insertion,
-            manual.template.clone(),
-            40,
-            first_last,
-        );
+        let counts = manual.checked_counts(40).unwrap();
         log::debug!("counts: {:?}", counts);
+        assert_eq!(counts.get(&Element('B')).unwrap(), &2192039569602);
+        assert_eq!(counts.get(&Element('H')).unwrap(), &3849876073);
+    }
+
+    #[test]
+    fn test_any_alphabet() {
+        let manual = parse("ab1\n\nab -> é\néb -> 1\n").unwrap();
         assert_eq!(
-            counts.get(&Element::from_char('B')).unwrap(),
-            &2192039569602
+            manual.alphabet(),
+            [Element('1'), Element('a'), Element('b'), Element('é')]
         );
-        assert_eq!(counts.get(&Element::from_char('H')).unwrap(), &3849876073);
+        let steps: Vec<BTreeMap<Element, u64>> = manual.count_steps().take(3).collect();
+        // b1 has no rule so is left alone
+        assert_eq!(steps[1], counts_of("aéb1"));
+        assert_eq!(steps[2], counts_of("aé1b1"));
+        assert!(parse("NN\n\nNNN -> C\n").is_err());
+        assert!(parse("NN\n\nNN -> C\nNN -> B\n").is_err());
+        assert!(parse("\n\nNN -> C\n").is_err());
+    }
+
+    #[test]
+    fn test_many_steps() {
+        let manual = parse(DAY.example).unwrap();
+        // The polymer roughly doubles each step
+        assert!(manual.checked_counts(60).is_ok());
+        assert_eq!(manual.checked_counts(70), Err(Overflow { steps: 70 }));
+        let counts: BTreeMap<Element, BigUint> = manual.counts(2000);
+        let total = counts.values().sum::<BigUint>();
+        assert_eq!(total, (BigUint::from(1u8) << 2000) * 3u8 + 1u8);
+        assert_eq!(counts.len(), 4);
     }
 
     #[test]
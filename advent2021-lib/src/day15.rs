@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashSet;
 
-use pathfinding::prelude::astar_bag;
+use pathfinding::prelude::{astar, astar_bag};
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
@@ -13,45 +15,156 @@ impl std::fmt::Debug for Risk {
     }
 }
 
-#[derive(Debug)]
-pub struct Map(HashMap<(usize, usize), Risk>);
+pub type Node = (usize, usize);
+type Cost = usize;
+
+/// Dense grid of risks, stored row by row
+#[derive(Clone, Debug)]
+pub struct Map {
+    width: usize,
+    risks: Vec<Risk>,
+}
 
 impl Map {
+    /// Width and height of the map
+    pub fn dimensions(&self) -> (usize, usize) {
+        if self.width == 0 {
+            return (0, 0);
+        }
+        (self.width, self.risks.len() / self.width)
+    }
+
+    fn get(&self, node: &Node) -> Option<usize> {
+        if node.0 >= self.width {
+            return None;
+        }
+        self.risks
+            .get(node.1 * self.width + node.0)
+            .map(|risk| risk.0)
+    }
+
+    fn node(&self, idx: usize) -> Node {
+        (idx % self.width, idx / self.width)
+    }
+
+    /// Neighbouring cells as indices into `risks`
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = usize> {
+        let width = self.width;
+        let len = self.risks.len();
+        let x = idx % width;
+        [
+            (x > 0).then(|| idx - 1),
+            (idx >= width).then(|| idx - width),
+            (x + 1 < width).then(|| idx + 1),
+            (idx + width < len).then(|| idx + width),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// The map tiled 5 times in each direction
+    pub fn expanded(&self) -> Self {
+        let mut map = self.clone();
+        map.full_map();
+        map
+    }
+
+    /// Risk map with the cells off the path replaced by `.`
+    pub fn render_ascii(&self, path: &[Node]) -> String {
+        let path: HashSet<&Node> = path.iter().collect();
+        let (width, height) = self.dimensions();
+        let mut ascii = String::with_capacity((width + 1) * height);
+        for y in 0..height {
+            for x in 0..width {
+                if path.contains(&(x, y)) {
+                    ascii.push_str(&self.get(&(x, y)).unwrap().to_string());
+                } else {
+                    ascii.push('.');
+                }
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    /// Risk map shaded from light (low risk) to dark (high risk), with the path drawn over it
+    pub fn render_svg(&self, path: &[Node]) -> String {
+        const CELL: usize = 10;
+        let (width, height) = self.dimensions();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            width * CELL,
+            height * CELL,
+            width * CELL,
+            height * CELL
+        );
+        for y in 0..height {
+            for x in 0..width {
+                let risk = self.get(&(x, y)).unwrap();
+                // Darker for higher risks, and risks of 0 as light as 1
+                let shade = 255 - risk.saturating_sub(1) * 24;
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"rgb({},{},{})\"><title>{}</title></rect>\n",
+                    x * CELL,
+                    y * CELL,
+                    CELL,
+                    CELL,
+                    shade,
+                    shade,
+                    shade,
+                    risk
+                ));
+            }
+        }
+        let points: Vec<String> = path
+            .iter()
+            .map(|(x, y)| format!("{},{}", x * CELL + CELL / 2, y * CELL + CELL / 2))
+            .collect();
+        svg.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"red\" stroke-width=\"{}\"/>\n",
+            points.join(" "),
+            CELL / 3
+        ));
+        svg.push_str("</svg>\n");
+        svg
+    }
+
     fn full_map(&mut self) {
-        log::trace!("map: {:?}", self.0);
-        let width = self.0.keys().max_by_key(|n| n.0).unwrap().0 + 1;
-        let height = self.0.keys().max_by_key(|n| n.1).unwrap().1 + 1;
-        for tile_x in 0..5 {
-            for tile_y in 0..5 {
-                for x in 0..width {
-                    for y in 0..height {
-                        let reference = self.0.get(&(x, y)).unwrap().0;
+        log::trace!("map: {:?}", self.risks);
+        let (width, height) = self.dimensions();
+        let mut risks = Vec::with_capacity(self.risks.len() * 25);
+        for tile_y in 0..5 {
+            for y in 0..height {
+                for tile_x in 0..5 {
+                    for x in 0..width {
+                        let reference = self.get(&(x, y)).unwrap();
                         let offset = tile_x + tile_y;
-                        let new = Risk(((reference + offset - 1) % 9) + 1);
-                        self.0
-                            .insert((tile_x * width + x, tile_y * height + y), new);
+                        risks.push(Risk(((reference + offset - 1) % 9) + 1));
                     }
                 }
             }
         }
-        log::trace!("full_map: {:?}", self.0);
+        self.width *= 5;
+        self.risks = risks;
+        log::trace!("full_map: {:?}", self.risks);
     }
 }
 
 impl std::str::FromStr for Map {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut map = HashMap::new();
-        for (row_idx, row) in s.lines().enumerate() {
-            for (col_idx, num) in row.chars().enumerate() {
-                map.insert(
-                    (col_idx, row_idx),
-                    Risk(num.to_string().parse().map_err(ParseError::Int)?),
-                );
+        let width = s.lines().next().ok_or(ParseError::Empty)?.len();
+        let mut risks = Vec::new();
+        for row in s.lines() {
+            if row.len() != width {
+                return Err(ParseError::Str(row.to_owned()));
+            }
+            for num in row.chars() {
+                risks.push(Risk(num.to_string().parse().map_err(ParseError::Int)?));
             }
         }
-        log::debug!("map: {:?}", map);
-        Ok(Self(map))
+        log::debug!("map: {:?}", risks);
+        Ok(Self { width, risks })
     }
 }
 
@@ -59,34 +172,16 @@ pub fn parse(input: &str) -> ParseResult<Map> {
     input.parse()
 }
 
-pub fn path(map: &Map) -> usize {
-    type Node = (usize, usize);
-    type Cost = usize;
+/// All lowest risk paths from the top left to the bottom right, and their total risk
+pub fn paths(map: &Map) -> (impl Iterator<Item = Vec<Node>>, usize) {
     let start: Node = (0, 0);
-    let end = map.0.keys().max_by_key(|n| n.0 * n.1).unwrap();
+    let (width, height) = map.dimensions();
+    let end = &(width - 1, height - 1);
     let successors = |node: &Node| -> Vec<(Node, Cost)> {
-        let mut successors = vec![];
-        let right = node.0.checked_add(1).unwrap();
-        let down = node.1.checked_add(1).unwrap();
-        let mut add_successor = |next| {
-            successors.push((next, map.0.get(&next).unwrap().0));
-        };
-        // left
-        if let Some(x) = node.0.checked_sub(1) {
-            add_successor((x, node.1));
-        }
-        // up
-        if let Some(y) = node.1.checked_sub(1) {
-            add_successor((node.0, y))
-        }
-        // right
-        if map.0.contains_key(&(right, node.1)) {
-            add_successor((right, node.1))
-        }
-        // down
-        if map.0.contains_key(&(node.0, down)) {
-            add_successor((node.0, down))
-        }
+        let successors: Vec<(Node, Cost)> = map
+            .neighbours(node.1 * width + node.0)
+            .map(|idx| (map.node(idx), map.risks[idx].0))
+            .collect();
         log::trace!("node: {:?}, successors: {:?}", node, successors);
         successors
     };
@@ -104,20 +199,164 @@ pub fn path(map: &Map) -> usize {
         log::trace!("node: {:?}, success: {:?}", node, node == end);
         node == end
     };
-    let (mut solutions, cost) = astar_bag(&start, successors, heuristic, success).unwrap();
+    let (solutions, cost) = astar_bag(&start, successors, heuristic, success).unwrap();
     log::debug!("solution cost {}", cost);
-    log::trace!("solution: {:?}", solutions.next());
+    (solutions, cost)
+}
+
+/// A lowest risk path and its total risk
+pub fn lowest_risk_path(map: &Map) -> (Vec<Node>, usize) {
+    let (mut solutions, cost) = paths(map);
+    let solution = solutions.next().unwrap();
+    log::trace!("solution: {:?}", solution);
+    (solution, cost)
+}
+
+/// Shortest path algorithm to find the lowest total risk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Dijkstra with a bucket queue, as risks are bounded by 9
+    Dijkstra,
+    /// A* with a Manhattan distance heuristic
+    AStar,
+    /// A* keeping track of all optimal paths
+    AStarBag,
+    /// Dijkstra from both corners, meeting in the middle
+    Bidirectional,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 4] = [
+        Algorithm::Dijkstra,
+        Algorithm::AStar,
+        Algorithm::AStarBag,
+        Algorithm::Bidirectional,
+    ];
+}
+
+/// Lowest total risk from the top left to the bottom right
+pub fn lowest_risk(map: &Map, algorithm: Algorithm) -> usize {
+    match algorithm {
+        Algorithm::Dijkstra => dijkstra(map),
+        Algorithm::AStar => astar_dense(map),
+        Algorithm::AStarBag => paths(map).1,
+        Algorithm::Bidirectional => bidirectional(map),
+    }
+}
+
+fn dijkstra(map: &Map) -> usize {
+    const BUCKETS: usize = 9 + 1;
+    let end = map.risks.len() - 1;
+    let mut costs = vec![usize::MAX; map.risks.len()];
+    // Every queued cost lies within the maximum risk of the current one, so buckets can be reused
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); BUCKETS];
+    costs[0] = 0;
+    buckets[0].push(0);
+    let mut cost = 0;
+    loop {
+        while let Some(idx) = buckets[cost % BUCKETS].pop() {
+            if costs[idx] != cost {
+                // stale entry
+                continue;
+            }
+            if idx == end {
+                return cost;
+            }
+            for next in map.neighbours(idx) {
+                let next_cost = cost + map.risks[next].0;
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    let bucket = next_cost % BUCKETS;
+                    buckets[bucket].push(next);
+                }
+            }
+        }
+        cost += 1;
+    }
+}
+
+fn astar_dense(map: &Map) -> usize {
+    let end = map.risks.len() - 1;
+    let end_node = map.node(end);
+    let (_path, cost) = astar(
+        &0,
+        |idx| {
+            map.neighbours(*idx)
+                .map(|next| (next, map.risks[next].0))
+                .collect::<Vec<_>>()
+        },
+        |idx| {
+            let node = map.node(*idx);
+            (end_node.0 - node.0) + (end_node.1 - node.1)
+        },
+        |idx| *idx == end,
+    )
+    .unwrap();
     cost
 }
 
+fn bidirectional(map: &Map) -> usize {
+    let end = map.risks.len() - 1;
+    // Entering a cell costs its risk, so backwards each step costs the risk of the cell left
+    let mut costs = [
+        vec![usize::MAX; map.risks.len()],
+        vec![usize::MAX; map.risks.len()],
+    ];
+    let mut queues = [BinaryHeap::new(), BinaryHeap::new()];
+    costs[0][0] = 0;
+    costs[1][end] = 0;
+    queues[0].push(Reverse((0, 0)));
+    queues[1].push(Reverse((0, end)));
+    let mut best = usize::MAX;
+    loop {
+        let frontier = |queue: &BinaryHeap<Reverse<(usize, usize)>>| {
+            queue.peek().map_or(usize::MAX, |Reverse((cost, _))| *cost)
+        };
+        let (forward, backward) = (frontier(&queues[0]), frontier(&queues[1]));
+        if forward.saturating_add(backward) >= best {
+            return best;
+        }
+        let direction = if forward <= backward { 0 } else { 1 };
+        let Reverse((cost, idx)) = queues[direction].pop().unwrap();
+        if cost > costs[direction][idx] {
+            continue;
+        }
+        // Also catches the searches starting on the same cell, on a 1x1 map
+        let other = costs[1 - direction][idx];
+        if other != usize::MAX {
+            best = best.min(cost + other);
+        }
+        for next in map.neighbours(idx) {
+            let step = if direction == 0 {
+                map.risks[next].0
+            } else {
+                map.risks[idx].0
+            };
+            let next_cost = cost + step;
+            if next_cost < costs[direction][next] {
+                costs[direction][next] = next_cost;
+                queues[direction].push(Reverse((next_cost, next)));
+                let other = costs[1 - direction][next];
+                if other != usize::MAX {
+                    best = best.min(next_cost + other);
+                }
+            }
+        }
+    }
+}
+
+pub fn path(map: &Map) -> usize {
+    lowest_risk(map, Algorithm::Dijkstra)
+}
+
 pub fn part1(map: &Map) -> PartOutput<usize> {
     PartOutput { answer: path(map) }
 }
 
 pub fn part2(map: &Map) -> PartOutput<usize> {
-    let mut map = Map(map.0.clone());
-    map.full_map();
-    PartOutput { answer: path(&map) }
+    PartOutput {
+        answer: path(&map.expanded()),
+    }
 }
 
 pub const DAY: Day<Map, usize> = Day {
@@ -150,20 +389,99 @@ mod tests {
     #[test]
     fn test_example_part2() {
         let mut map = parse(DAY.example).unwrap();
-        assert_eq!(map.0.len(), 100);
+        assert_eq!(map.risks.len(), 100);
         map.full_map();
-        assert_eq!(map.0.len(), 2500);
-        assert_eq!(map.0.get(&(49, 49)).unwrap().0, 9);
-        assert_eq!(map.0.get(&(0, 49)).unwrap().0, 6);
-        assert_eq!(map.0.get(&(1, 48)).unwrap().0, 6);
+        assert_eq!(map.risks.len(), 2500);
+        assert_eq!(map.get(&(49, 49)).unwrap(), 9);
+        assert_eq!(map.get(&(0, 49)).unwrap(), 6);
+        assert_eq!(map.get(&(1, 48)).unwrap(), 6);
         let result = path(&map);
         assert_eq!(result, 315);
     }
 
+    #[test]
+    fn test_algorithms() {
+        let map = parse(DAY.example).unwrap();
+        let expanded = map.expanded();
+        for algorithm in Algorithm::ALL {
+            assert_eq!(lowest_risk(&map, algorithm), 40, "{:?}", algorithm);
+            assert_eq!(lowest_risk(&expanded, algorithm), 315, "{:?}", algorithm);
+        }
+        // Cheapest route doubles back up and left
+        let winding: Map = "1111\n9991\n1111\n1999\n1111".parse().unwrap();
+        for algorithm in Algorithm::ALL {
+            assert_eq!(lowest_risk(&winding, algorithm), 13, "{:?}", algorithm);
+        }
+        // The start is the end, and its risk is never entered
+        let single: Map = "5".parse().unwrap();
+        for algorithm in Algorithm::ALL {
+            assert_eq!(lowest_risk(&single, algorithm), 0, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_example_lowest_risk_path() {
+        let map = parse(DAY.example).unwrap();
+        let (solution, cost) = lowest_risk_path(&map);
+        assert_eq!(cost, 40);
+        assert_eq!(solution.first(), Some(&(0, 0)));
+        assert_eq!(solution.last(), Some(&(9, 9)));
+        for step in solution.windows(2) {
+            let distance = step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1);
+            assert_eq!(distance, 1);
+        }
+        let risk: usize = solution
+            .iter()
+            .skip(1)
+            .map(|node| map.get(node).unwrap())
+            .sum();
+        assert_eq!(risk, cost);
+        let (solutions, _cost) = paths(&map);
+        assert!(solutions.count() >= 1);
+    }
+
+    #[test]
+    fn test_example_render() {
+        let map = parse(DAY.example).unwrap();
+        let path = [(0, 0), (0, 1), (0, 2), (1, 2)];
+        let ascii = map.render_ascii(&path);
+        assert_eq!(ascii.lines().count(), 10);
+        assert_eq!(ascii.lines().next(), Some("1........."));
+        assert_eq!(ascii.lines().nth(2), Some("21........"));
+        let svg = map.render_svg(&path);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 100);
+        assert!(svg.contains("points=\"5,5 5,15 5,25 15,25\""));
+        let zero = parse("09\n10")
+            .unwrap()
+            .render_svg(&[(0, 0), (0, 1), (1, 1)]);
+        assert!(zero.contains("fill=\"rgb(255,255,255)\"><title>0</title>"));
+        assert!(zero.contains("fill=\"rgb(63,63,63)\"><title>9</title>"));
+        let expanded = map.expanded();
+        assert_eq!(expanded.dimensions(), (50, 50));
+        let (solution, _cost) = lowest_risk_path(&expanded);
+        assert_eq!(
+            expanded
+                .render_ascii(&solution)
+                .chars()
+                .filter(|c| c.is_ascii_digit())
+                .count(),
+            solution.len()
+        );
+    }
+
     #[test]
     fn test_main() {
         let map = parse(&get_input(15)).unwrap();
         assert_eq!(part1(&map).answer.to_string(), "613");
         assert_eq!(part2(&map).answer.to_string(), "2899");
     }
+
+    #[test]
+    fn test_main_algorithms() {
+        let map = parse(&get_input(15)).unwrap().expanded();
+        for algorithm in Algorithm::ALL {
+            assert_eq!(lowest_risk(&map, algorithm), 2899, "{:?}", algorithm);
+        }
+    }
 }
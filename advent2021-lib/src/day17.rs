@@ -3,11 +3,11 @@ use serde::Deserialize;
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
-#[derive(Clone, Debug)]
-pub struct Position(isize, isize);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position(pub isize, pub isize);
 
-#[derive(Clone, Debug)]
-pub struct Velocity(isize, isize);
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity(pub isize, pub isize);
 
 pub fn step(position: &Position, velocity: &Velocity) -> (Position, Velocity) {
     (
@@ -16,10 +16,24 @@ pub fn step(position: &Position, velocity: &Velocity) -> (Position, Velocity) {
     )
 }
 
+/// The sum 1 + 2 + ... + n, how far a probe launched at n goes before stopping
+fn triangle(n: isize) -> isize {
+    n * (n + 1) / 2
+}
+
+/// The smallest n where `triangle(n) >= distance`
+fn inverse_triangle(distance: isize) -> isize {
+    let mut n = ((2.0 * distance as f64).sqrt() as isize).saturating_sub(1);
+    while triangle(n) < distance {
+        n += 1;
+    }
+    n
+}
+
 impl Velocity {
     pub fn steps(&self, n: usize) -> Position {
         let mut position = Position(0, 0);
-        let mut velocity = Self(self.0, self.1);
+        let mut velocity = *self;
         for _ in 0..n {
             let (new_position, new_velocity) = step(&position, &velocity);
             position = new_position;
@@ -31,18 +45,32 @@ impl Velocity {
     /// Max height is when y = 0
     /// which is when t = y
     /// Therefore max_height = u + (u-1) + (u-2) ...
+    /// Probes launched downwards never get above the launcher
     pub fn max_height(&self) -> usize {
-        (0..=self.1.try_into().unwrap()).sum()
+        triangle(self.1.max(0)) as usize
+    }
+
+    /// Where the probe comes to rest horizontally
+    pub fn rest_x(&self) -> isize {
+        self.0.signum() * triangle(self.0.abs())
+    }
+}
+
+/// The probe can stop horizontally inside a target that spans the launcher's
+/// height, so every steep enough launch falls back through it
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnboundedTarget;
+
+impl std::fmt::Display for UnboundedTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "infinitely many launch velocities reach the target")
     }
 }
 
-///
-/// Current assumption:
-/// x is positive
-/// y is negative
+/// A target rectangle anywhere relative to the launcher at the origin
 #[derive(Debug, Deserialize, Recap)]
 #[recap(
-    regex = r#"^target area: x=(?P<start_x>\d+)..(?P<end_x>\d+), y=(?P<start_y>-\d+)..(?P<end_y>-\d+)$"#
+    regex = r#"^target area: x=(?P<start_x>-?\d+)..(?P<end_x>-?\d+), y=(?P<start_y>-?\d+)..(?P<end_y>-?\d+)$"#
 )]
 pub struct TargetArea {
     start_x: isize,
@@ -52,27 +80,53 @@ pub struct TargetArea {
 }
 
 impl TargetArea {
-    fn closest_x(&self) -> isize {
+    pub fn new(x: (isize, isize), y: (isize, isize)) -> Self {
+        Self {
+            start_x: x.0,
+            end_x: x.1,
+            start_y: y.0,
+            end_y: y.1,
+        }
+    }
+
+    pub fn min_x(&self) -> isize {
         std::cmp::min(self.start_x, self.end_x)
     }
 
-    fn furthest_x(&self) -> isize {
+    pub fn max_x(&self) -> isize {
         std::cmp::max(self.start_x, self.end_x)
     }
 
-    fn lowest_y(&self) -> isize {
+    pub fn min_y(&self) -> isize {
         std::cmp::min(self.start_y, self.end_y)
     }
 
-    fn check(&self, mut velocity: Velocity) -> bool {
-        log::debug!("check: {:?}", velocity);
+    pub fn max_y(&self) -> isize {
+        std::cmp::max(self.start_y, self.end_y)
+    }
+
+    pub fn contains(&self, position: &Position) -> bool {
+        (self.min_x()..=self.max_x()).contains(&position.0)
+            && (self.min_y()..=self.max_y()).contains(&position.1)
+    }
+
+    /// Whether the probe could still reach the target
+    fn reachable(&self, position: &Position, velocity: &Velocity) -> bool {
+        let falling_below = position.1 < self.min_y() && velocity.1 <= 0;
+        let passed_x = match velocity.0.signum() {
+            1 => position.0 > self.max_x(),
+            -1 => position.0 < self.min_x(),
+            _ => !(self.min_x()..=self.max_x()).contains(&position.0),
+        };
+        !falling_below && !passed_x
+    }
+
+    pub fn check(&self, mut velocity: Velocity) -> bool {
+        log::trace!("check: {:?}", velocity);
         let mut position = Position(0, 0);
-        while position.0 < self.furthest_x() && position.1 > self.lowest_y() {
-            log::trace!("check({:?}, {:?})", position, velocity);
+        while self.reachable(&position, &velocity) {
             let (new_position, new_velocity) = step(&position, &velocity);
-            if (self.start_x..=self.end_x).contains(&new_position.0)
-                && (self.start_y..=self.end_y).contains(&new_position.1)
-            {
+            if self.contains(&new_position) {
                 return true;
             }
             position = new_position;
@@ -81,120 +135,252 @@ impl TargetArea {
         false
     }
 
-    /// Get the minimum initial horizontal speed
-    /// this is equal to the time it would take to stop at the closest_x
-    /// which is minimising u for (0..=u).sum() >= closest_x
+    /// Range of horizontal launch speeds that can reach the target
     ///
-    fn min_vel_x(&self) -> isize {
-        let mut u: isize = self.closest_x() / 2;
-        loop {
-            let x: isize = (0..=u).sum();
-            if x < self.closest_x() {
-                break u + 1;
-            }
-            u -= 1;
+    /// The probe never turns back, so overshooting on the first step is too fast.
+    /// Launched at u it stops after travelling `triangle(u)`, so that has to reach
+    /// the nearest edge. Targets straddling x = 0 can be hit in either direction.
+    fn vel_x_bounds(&self) -> (isize, isize) {
+        if self.min_x() > 0 {
+            (inverse_triangle(self.min_x()), self.max_x())
+        } else if self.max_x() < 0 {
+            (self.min_x(), -inverse_triangle(-self.max_x()))
+        } else {
+            (self.min_x(), self.max_x())
         }
     }
 
-    /// Get the maximum initial horizontal speed
-    /// this is equal to stepping to furthest_x
-    ///
-    fn max_vel_x(&self) -> isize {
-        self.furthest_x()
-    }
-
-    /// Get the maximum initial horizontal speed
-    /// this is equal to the time it would take to stop at the end
-    /// which is maximising u for (0..=u).sum() <= furthest_x
+    /// Range of vertical launch speeds that can reach the target
     ///
-    fn furthest_stop_vel_x(&self) -> isize {
-        let mut u: isize = 0;
-        loop {
-            let x: isize = (0..=u).sum();
-            if x > self.furthest_x() {
-                break u - 1;
+    /// A probe launched up at u passes back through y = 0 at -(u + 1), so
+    /// for targets below it must not skip straight past the bottom edge.
+    /// For targets above, the apex `triangle(u)` has to reach the bottom edge
+    /// and the first step must not overshoot the top. Targets straddling y = 0
+    /// are only bounded by the time the probe spends over them horizontally.
+    fn vel_y_bounds(&self) -> Result<(isize, isize), UnboundedTarget> {
+        let (min_y, max_y) = (self.min_y(), self.max_y());
+        if max_y < 0 {
+            Ok((min_y, -min_y - 1))
+        } else if min_y > 0 {
+            Ok((inverse_triangle(min_y), max_y))
+        } else {
+            let (min_x, max_x) = self.vel_x_bounds();
+            if (min_x..=max_x)
+                .any(|x| (self.min_x()..=self.max_x()).contains(&Velocity(x, 0).rest_x()))
+            {
+                return Err(UnboundedTarget);
             }
-            u += 1;
+            // Horizontal motion stops outside the target within `time` steps,
+            // and faster launches are still climbing above the target by then
+            let time = self.min_x().abs().max(self.max_x().abs());
+            Ok((min_y, time.max(2 * max_y).max(-min_y - 1)))
         }
     }
 
-    /// Get minimum vertical (largest negative) speed at launch
-    ///
-    /// We therefore are looking for a minimum u, where u >= lowest_y
-    /// u = lowest_y
-    ///
-    fn min_vel_y(&self) -> isize {
-        self.lowest_y()
+    pub fn velocity_bounds(&self) -> Result<(Velocity, Velocity), UnboundedTarget> {
+        let (min_x, max_x) = self.vel_x_bounds();
+        let (min_y, max_y) = self.vel_y_bounds()?;
+        Ok((Velocity(min_x, min_y), Velocity(max_x, max_y)))
     }
 
-    /// Get maximum vertical speed at launch
-    ///
-    /// Given infinite time,
-    /// the probe will always arrive at y=0 at the same speed as it was launched.
-    /// The next step will take the probe to u+1
-    /// We therefore are looking for a maximum u, where u+1 <= |lowest_y|
-    /// u = |lowest_y| - 1
-    ///
-    fn max_vel_y(&self) -> isize {
-        assert!(self.lowest_y() < 0);
-        self.lowest_y().checked_neg().unwrap() - 1
-    }
-
-    /// Maximum X is used just to give the probe more time in the sweet spot
-    fn max_height(&self) -> usize {
-        let furthest_stop_vel_x = self.furthest_stop_vel_x();
-        let max_vel_y = self.max_vel_y();
-        if log::log_enabled!(log::Level::Trace) {
-            for incr in 0..5 {
-                let u = Velocity(furthest_stop_vel_x + incr, max_vel_y + incr);
-                log::trace!(
-                    "max_vel: {:?} -> {:?}, {:?}",
-                    u,
-                    u.steps((u.0).try_into().unwrap()),
-                    self.check(u.clone()),
-                );
-            }
-        }
-        let vel = Velocity(furthest_stop_vel_x, max_vel_y);
+    /// The velocity giving the highest arc that still hits the target
+    pub fn highest_velocity(&self) -> Result<Velocity, UnboundedTarget> {
+        let (min, max) = self.velocity_bounds()?;
+        Ok((min.1..=max.1)
+            .rev()
+            .find_map(|y| {
+                (min.0..=max.0)
+                    .map(|x| Velocity(x, y))
+                    .find(|vel| self.check(*vel))
+            })
+            .expect("launching straight at the target hits it on the first step"))
+    }
+
+    pub fn max_height(&self) -> Result<usize, UnboundedTarget> {
+        let vel = self.highest_velocity()?;
         let height = vel.max_height();
-        log::debug!("max height: {:?} -> {:?}", vel, height,);
-        if !self.check(vel) {
-            panic!();
-        }
-        height
+        log::debug!("max height: {:?} -> {:?}", vel, height);
+        Ok(height)
     }
 
-    /// Maximum X is used just to give the probe more time in the sweet spot
-    fn get_velocities(&self) -> Vec<Velocity> {
+    pub fn get_velocities(&self) -> Result<Vec<Velocity>, UnboundedTarget> {
+        let (min, max) = self.velocity_bounds()?;
         let mut velocities = vec![];
-        for y in self.min_vel_y()..=self.max_vel_y() {
-            for x in self.min_vel_x()..=self.max_vel_x() {
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
                 let vel = Velocity(x, y);
-                if self.check(vel.clone()) {
+                if self.check(vel) {
                     velocities.push(vel);
                 }
             }
         }
-        velocities
+        Ok(velocities)
+    }
+}
+
+/// The positions of a probe after each step, starting from the launcher
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trajectory {
+    pub velocity: Velocity,
+    pub positions: Vec<Position>,
+    /// Whether the last position is in the target
+    pub hit: bool,
+}
+
+impl Trajectory {
+    pub fn apex(&self) -> isize {
+        self.positions.iter().map(|pos| pos.1).max().unwrap_or(0)
+    }
+}
+
+/// Corners of the smallest box around the launcher, the target and the points
+fn bounds<'a>(
+    target_area: &TargetArea,
+    points: impl Iterator<Item = &'a Position>,
+) -> (Position, Position) {
+    let mut min = Position(target_area.min_x().min(0), target_area.min_y().min(0));
+    let mut max = Position(target_area.max_x().max(0), target_area.max_y().max(0));
+    for point in points {
+        min = Position(min.0.min(point.0), min.1.min(point.1));
+        max = Position(max.0.max(point.0), max.1.max(point.1));
     }
+    (min, max)
 }
 
+impl TargetArea {
+    /// Follows the probe until it hits the target or can no longer reach it
+    pub fn trajectory(&self, velocity: Velocity) -> Trajectory {
+        let mut positions = vec![Position(0, 0)];
+        let mut current = (Position(0, 0), velocity);
+        while self.reachable(&current.0, &current.1) {
+            current = step(&current.0, &current.1);
+            positions.push(current.0);
+            if self.contains(&current.0) {
+                return Trajectory {
+                    velocity,
+                    positions,
+                    hit: true,
+                };
+            }
+        }
+        Trajectory {
+            velocity,
+            positions,
+            hit: false,
+        }
+    }
+
+    /// The trajectory drawn like the puzzle, with `S` for the launcher,
+    /// `#` for the probe and `T` for the target
+    pub fn render_ascii(&self, trajectory: &Trajectory) -> String {
+        let (min, max) = bounds(self, trajectory.positions.iter());
+        let mut ascii = String::new();
+        for y in (min.1..=max.1).rev() {
+            for x in min.0..=max.0 {
+                let position = Position(x, y);
+                ascii.push(if position == Position(0, 0) {
+                    'S'
+                } else if trajectory.positions.contains(&position) {
+                    '#'
+                } else if self.contains(&position) {
+                    'T'
+                } else {
+                    '.'
+                });
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    /// The target with the given shots drawn over it, and each of `velocities`
+    /// as a point, since launching at a velocity first lands on that point
+    pub fn render_svg(&self, shots: &[Trajectory], velocities: &[Velocity]) -> String {
+        const COLOURS: [&str; 4] = ["crimson", "darkorange", "seagreen", "royalblue"];
+        let velocity_points: Vec<Position> = velocities
+            .iter()
+            .map(|vel| Position(vel.0, vel.1))
+            .collect();
+        let (min, max) = bounds(
+            self,
+            shots
+                .iter()
+                .flat_map(|shot| shot.positions.iter())
+                .chain(velocity_points.iter()),
+        );
+        let (width, height) = (max.0 - min.0, max.1 - min.1);
+        let unit = (width.max(height) as f64 / 300.0).max(0.2);
+        let margin = 4.0 * unit;
+        // y increases upwards, so flip it over
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"600\" viewBox=\"{} {} {} {}\">\n<g transform=\"scale(1,-1)\">\n",
+            min.0 as f64 - margin,
+            -max.1 as f64 - margin,
+            width as f64 + 2.0 * margin,
+            height as f64 + 2.0 * margin
+        );
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"lightsteelblue\"/>\n",
+            self.min_x(),
+            self.min_y(),
+            self.max_x() - self.min_x(),
+            self.max_y() - self.min_y()
+        ));
+        for vel in &velocity_points {
+            svg.push_str(&format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"0.4\" fill=\"slategray\"/>\n",
+                vel.0, vel.1
+            ));
+        }
+        for (shot, colour) in shots.iter().zip(COLOURS.iter().cycle()) {
+            let points: Vec<String> = shot
+                .positions
+                .iter()
+                .map(|pos| format!("{},{}", pos.0, pos.1))
+                .collect();
+            svg.push_str(&format!(
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"><title>{:?}</title></polyline>\n",
+                points.join(" "),
+                colour,
+                unit / 2.0,
+                shot.velocity
+            ));
+        }
+        svg.push_str(&format!(
+            "<circle cx=\"0\" cy=\"0\" r=\"{}\" fill=\"black\"/>\n",
+            2.0 * unit
+        ));
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+}
+
+/// Parse a target area with finitely many launch velocities reaching it
 pub fn parse(input: &str) -> ParseResult<TargetArea> {
-    input
+    let target_area: TargetArea = input
         .trim()
         .parse()
-        .map_err(|_| ParseError::Str(input.to_owned()))
+        .map_err(|_| ParseError::Str(input.to_owned()))?;
+    target_area
+        .velocity_bounds()
+        .map_err(|e| ParseError::Str(format!("{}: {}", e, input)))?;
+    Ok(target_area)
 }
 
 pub fn part1(target_area: &TargetArea) -> PartOutput<usize> {
     PartOutput {
-        answer: target_area.max_height(),
+        answer: target_area
+            .max_height()
+            .expect("parsing rejects unbounded targets"),
     }
 }
 
 pub fn part2(target_area: &TargetArea) -> PartOutput<usize> {
     PartOutput {
-        answer: target_area.get_velocities().len(),
+        answer: target_area
+            .get_velocities()
+            .expect("parsing rejects unbounded targets")
+            .len(),
     }
 }
 
@@ -236,13 +422,108 @@ mod tests {
     #[test]
     fn test_example_part1() {
         let target_area = parse(DAY.example).unwrap();
-        assert_eq!(target_area.max_height(), 45);
+        assert_eq!(target_area.max_height(), Ok(45));
     }
 
     #[test]
     fn test_example_part2() {
         let target_area = parse(DAY.example).unwrap();
-        assert_eq!(target_area.get_velocities().len(), 112);
+        assert_eq!(target_area.get_velocities().unwrap().len(), 112);
+    }
+
+    /// Every velocity in a generous box, simulated for a fixed number of steps
+    fn brute_force(target_area: &TargetArea) -> Vec<Velocity> {
+        let mut velocities = vec![];
+        for y in -40..=40 {
+            for x in -40..=40 {
+                let (mut position, mut velocity) = (Position(0, 0), Velocity(x, y));
+                for _ in 0..200 {
+                    let (new_position, new_velocity) = step(&position, &velocity);
+                    if target_area.contains(&new_position) {
+                        velocities.push(Velocity(x, y));
+                        break;
+                    }
+                    position = new_position;
+                    velocity = new_velocity;
+                }
+            }
+        }
+        velocities
+    }
+
+    #[test]
+    fn test_quadrants_match_brute_force() {
+        let targets = [
+            ((20, 30), (-10, -5)),
+            ((-30, -20), (-10, -5)),
+            ((20, 30), (5, 10)),
+            ((-30, -20), (5, 10)),
+            ((-5, 5), (-10, -5)),
+            ((-5, 5), (5, 10)),
+            ((22, 27), (-3, 4)),
+            ((-27, -22), (-3, 4)),
+            ((7, 9), (-6, 2)),
+        ];
+        for (x, y) in targets {
+            let target_area = TargetArea::new(x, y);
+            let mut velocities = target_area.get_velocities().unwrap();
+            let mut expected = brute_force(&target_area);
+            velocities.sort_by_key(|vel| (vel.1, vel.0));
+            expected.sort_by_key(|vel| (vel.1, vel.0));
+            assert_eq!(velocities, expected, "{:?}", target_area);
+            let highest = expected.iter().map(Velocity::max_height).max().unwrap();
+            assert_eq!(target_area.max_height(), Ok(highest), "{:?}", target_area);
+        }
+    }
+
+    #[test]
+    fn test_unbounded() {
+        // Launching at 4 stops at x = 10, inside the target, so any steep shot works
+        let input = "target area: x=9..11, y=-2..3";
+        let unbounded: TargetArea = input.parse().unwrap();
+        assert_eq!(unbounded.get_velocities(), Err(UnboundedTarget));
+        assert!(matches!(parse(input), Err(ParseError::Str(_))));
+        // Launching straight at a single point always gets there
+        let point = TargetArea::new((11, 11), (1, 1));
+        assert!(point.check(Velocity(11, 1)));
+        assert_eq!(
+            point.get_velocities().unwrap().len(),
+            brute_force(&point).len()
+        );
+    }
+
+    #[test]
+    fn test_example_trajectory() {
+        let target_area = parse(DAY.example).unwrap();
+        let trajectory = target_area.trajectory(Velocity(7, 2));
+        assert!(trajectory.hit);
+        assert_eq!(trajectory.positions.len(), 8);
+        assert_eq!(trajectory.apex(), 3);
+        assert_eq!(
+            target_area.render_ascii(&trajectory),
+            "\
+.............#....#............
+.......#..............#........
+...............................
+S........................#.....
+...............................
+...............................
+...........................#...
+...............................
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTT#TT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+"
+        );
+        let miss = target_area.trajectory(Velocity(17, -4));
+        assert!(!miss.hit);
+        assert_eq!(miss.positions.last(), Some(&Position(33, -9)));
+        let svg = target_area.render_svg(&[trajectory, miss], &[Velocity(6, 9)]);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("<circle").count(), 2);
     }
 
     #[test]
@@ -1,215 +1,384 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum SnailfishElement {
-    Value(usize),
-    Pair(Box<SnailfishNumber>),
+/// Pairs nested inside this many pairs explode
+const MAX_NESTING: usize = 4;
+
+/// A regular number, with the number of pairs it is nested in
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Regular {
+    value: usize,
+    depth: usize,
 }
 
-impl std::str::FromStr for SnailfishElement {
-    type Err = ParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(if let Some('[') = s.chars().next() {
-            Self::Pair(Box::new(s.parse()?))
-        } else {
-            Self::Value(s.parse().map_err(ParseError::Int)?)
-        })
+/// A snailfish number stored as its regular numbers from left to right.
+///
+/// The pair structure follows from the depths,
+/// as long as pairs are nested at most one level deeper than they may be after reduction,
+/// which holds for reduced numbers and the sum of two of them.
+#[derive(Clone, PartialEq)]
+pub struct SnailfishNumber(Vec<Regular>);
+
+impl std::fmt::Debug for SnailfishNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
     }
 }
 
-impl SnailfishElement {
-    pub fn explode(&mut self, nesting: usize) -> Option<(Option<usize>, Option<usize>)> {
-        match self {
-            Self::Value(_val) => None,
-            Self::Pair(number) => number.explode(nesting + 1),
-        }
+impl std::fmt::Display for SnailfishNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut regulars = self.0.iter().peekable();
+        write_pair(f, &mut regulars, 0)
     }
+}
 
-    // Pop pop
-    pub fn magnitude(&self) -> usize {
-        match self {
-            Self::Value(val) => *val,
-            Self::Pair(number) => number.magnitude(),
+fn write_pair<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    regulars: &mut Peekable<impl Iterator<Item = &'a Regular>>,
+    depth: usize,
+) -> std::fmt::Result {
+    write!(f, "[")?;
+    write_element(f, regulars, depth + 1)?;
+    write!(f, ",")?;
+    write_element(f, regulars, depth + 1)?;
+    write!(f, "]")
+}
+
+fn write_element<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    regulars: &mut Peekable<impl Iterator<Item = &'a Regular>>,
+    depth: usize,
+) -> std::fmt::Result {
+    match regulars.peek() {
+        Some(regular) if regular.depth == depth => {
+            write!(f, "{}", regular.value)?;
+            regulars.next();
+            Ok(())
         }
+        Some(_) => write_pair(f, regulars, depth),
+        None => Err(std::fmt::Error),
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct SnailfishNumber(SnailfishElement, SnailfishElement);
-
 impl std::str::FromStr for SnailfishNumber {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        assert!(matches!(s.chars().next(), Some('[')));
-        let (elements, nesting) = s.chars().skip(1).fold(
-            (vec![String::new()], Some(0usize)),
-            |(mut s, nesting), c| {
-                if let Some(nesting) = nesting {
-                    s.last_mut().unwrap().push(c);
-                    match c {
-                        '[' => (s, Some(nesting + 1)),
-                        ']' => {
-                            if nesting > 0 {
-                                (s, Some(nesting - 1))
-                            } else {
-                                s.last_mut().unwrap().pop();
-                                (s, None)
-                            }
-                        }
-                        ',' => {
-                            if nesting == 0 {
-                                s.last_mut().unwrap().pop();
-                                s.push(String::new());
-                            }
-                            (s, Some(nesting))
-                        }
-                        '0'..='9' => (s, Some(nesting)),
-                        _ => panic!(),
-                    }
-                } else {
-                    s.push("Big Ooof".to_owned());
-                    (s, None)
-                }
-            },
-        );
-        if nesting.is_some() {
-            return Err(ParseError::Str("parsing unclosed".to_owned()));
+        let mut chars = s.chars().peekable();
+        let mut regulars = Vec::new();
+        if chars.peek() != Some(&'[') {
+            return Err(ParseError::Str("parsing not pair".to_owned()));
         }
-        if elements.last().unwrap() == "Big Ooof" {
+        parse_pair(&mut chars, 0, &mut regulars)?;
+        if chars.next().is_some() {
             return Err(ParseError::Str("parsing extra".to_owned()));
         }
-        if elements.len() != 2 {
-            return Err(ParseError::Str("parsing not pair".to_owned()));
-        }
-        let number = Self(elements[0].parse()?, elements[1].parse()?);
-        log::trace!("{:?} -> {:?}", (&elements, &nesting), number);
+        let number = Self(regulars);
+        log::trace!("{:?} -> {:?}", s, number.0);
         Ok(number)
     }
 }
 
-impl std::ops::Add for SnailfishNumber {
-    type Output = Self;
-    fn add(self, other: Self) -> Self {
-        let mut combined = SnailfishNumber(
-            SnailfishElement::Pair(Box::new(self)),
-            SnailfishElement::Pair(Box::new(other)),
-        );
-        loop {
-            if combined.explode(0).is_some() {
-                continue;
-            }
-            if combined.split() {
-                continue;
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> ParseResult<()> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(ParseError::Str(format!(
+            "parsing expected {:?}, found {:?}",
+            expected, c
+        ))),
+        None => Err(ParseError::Str("parsing unclosed".to_owned())),
+    }
+}
+
+fn parse_pair(
+    chars: &mut Peekable<Chars>,
+    depth: usize,
+    regulars: &mut Vec<Regular>,
+) -> ParseResult<()> {
+    if depth > MAX_NESTING {
+        return Err(ParseError::Str("parsing too deep".to_owned()));
+    }
+    expect(chars, '[')?;
+    parse_element(chars, depth + 1, regulars)?;
+    expect(chars, ',')?;
+    parse_element(chars, depth + 1, regulars)?;
+    expect(chars, ']')
+}
+
+fn parse_element(
+    chars: &mut Peekable<Chars>,
+    depth: usize,
+    regulars: &mut Vec<Regular>,
+) -> ParseResult<()> {
+    match chars.peek() {
+        Some('[') => parse_pair(chars, depth, regulars),
+        Some(c) if c.is_ascii_digit() => {
+            let mut digits = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                digits.push(c);
             }
-            break combined;
+            regulars.push(Regular {
+                value: digits.parse().map_err(ParseError::Int)?,
+                depth,
+            });
+            Ok(())
         }
+        Some(c) => Err(ParseError::Str(format!("parsing unexpected {:?}", c))),
+        None => Err(ParseError::Str("parsing unclosed".to_owned())),
     }
 }
 
-impl SnailfishNumber {
-    fn get_pair(&self) -> Option<(usize, usize)> {
-        if let SnailfishElement::Value(left) = self.0 {
-            if let SnailfishElement::Value(right) = self.1 {
-                return Some((left, right));
+/// Which element of a pair to descend into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Location of an element, descending from the outermost pair
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ElementPath(pub Vec<Side>);
+
+/// Printed as the sides taken, e.g. `LRR`, or `-` for the outermost pair
+impl std::fmt::Display for ElementPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "-");
+        }
+        for side in &self.0 {
+            match side {
+                Side::Left => write!(f, "L")?,
+                Side::Right => write!(f, "R")?,
             }
         }
-        None
+        Ok(())
     }
+}
 
-    pub fn explode(&mut self, nesting: usize) -> Option<(Option<usize>, Option<usize>)> {
-        log::trace!("explode {} {:?}", nesting, self);
-        if nesting >= 4 {
-            if let Some(pair) = self.get_pair() {
-                return Some((Some(pair.0), Some(pair.1)));
+/// A single action taken while reducing a snailfish number
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reduction {
+    /// The pair `[left,right]` at `path` exploded
+    Explode {
+        path: ElementPath,
+        left: usize,
+        right: usize,
+    },
+    /// The regular number `value` at `path` split
+    Split { path: ElementPath, value: usize },
+}
+
+impl std::fmt::Display for Reduction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Explode { path, left, right } => {
+                write!(f, "explode [{},{}] at {}", left, right, path)
             }
+            Self::Split { path, value } => write!(f, "split {} at {}", value, path),
         }
-        if let Some(pair) = self.0.explode(nesting) {
-            if let Some(right) = pair.1 {
-                if let Some(_left) = pair.0 {
-                    // the entire exploding pair is replaced with the regular number 0
-                    self.0 = SnailfishElement::Value(0);
-                }
-                // pair's right value is added to the first regular number to the right of the exploding pair (if any)
-                match &mut self.1 {
-                    SnailfishElement::Value(val) => self.1 = SnailfishElement::Value(*val + right),
-                    SnailfishElement::Pair(number) => number.add_left(right),
-                };
-                // pair's left value
-                return Some((pair.0, None));
-            }
-            return Some((pair.0, pair.1));
+    }
+}
+
+/// A reduction action with the number before and after it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReductionStep {
+    pub reduction: Reduction,
+    pub before: SnailfishNumber,
+    pub after: SnailfishNumber,
+}
+
+/// Iterator over the reduction actions of a snailfish number, until it is reduced
+pub struct ReductionSteps(SnailfishNumber);
+
+impl Iterator for ReductionSteps {
+    type Item = ReductionStep;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let before = self.0.clone();
+        let reduction = self.0.reduce_once()?;
+        log::debug!("{}: {}", reduction, self.0);
+        Some(ReductionStep {
+            reduction,
+            before,
+            after: self.0.clone(),
+        })
+    }
+}
+
+impl std::ops::Add for SnailfishNumber {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        let mut combined = self.concat(other);
+        while combined.reduce_once().is_some() {}
+        combined
+    }
+}
+
+impl<'a> std::ops::Add<&'a SnailfishNumber> for &'a SnailfishNumber {
+    type Output = SnailfishNumber;
+    fn add(self, other: Self) -> SnailfishNumber {
+        self.clone() + other.clone()
+    }
+}
+
+/// Panics on an empty iterator, as there is no snailfish zero
+impl std::iter::Sum for SnailfishNumber {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.reduce(|acc, next| acc + next)
+            .expect("cannot sum no snailfish numbers")
+    }
+}
+
+/// Panics on an empty iterator, as there is no snailfish zero
+impl<'a> std::iter::Sum<&'a SnailfishNumber> for SnailfishNumber {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.cloned().sum()
+    }
+}
+
+impl SnailfishNumber {
+    /// Parse a number that is already reduced, as the homework's numbers are,
+    /// so adding it to another reduced number keeps the pair structure intact
+    pub fn parse_reduced(s: &str) -> ParseResult<Self> {
+        let number: Self = s.parse()?;
+        if number.0.iter().any(|r| r.depth > MAX_NESTING) {
+            return Err(ParseError::Str(format!("not reduced, too deep: {}", s)));
         }
-        if let Some(pair) = self.1.explode(nesting) {
-            if let Some(left) = pair.0 {
-                if let Some(_right) = pair.1 {
-                    // the entire exploding pair is replaced with the regular number 0
-                    self.1 = SnailfishElement::Value(0);
-                }
-                // pair's left value is added to the first regular number to the left of the exploding pair
-                match &mut self.0 {
-                    SnailfishElement::Value(val) => self.0 = SnailfishElement::Value(*val + left),
-                    SnailfishElement::Pair(number) => number.add_right(left),
-                };
-                // pair's right value
-                return Some((None, pair.1));
-            }
-            return Some((pair.0, pair.1));
+        if number.0.iter().any(|r| r.value >= 10) {
+            return Err(ParseError::Str(format!("not reduced, too big: {}", s)));
         }
-        None
-    }
-
-    fn add_left(&mut self, add: usize) {
-        match &mut self.0 {
-            SnailfishElement::Value(val) => self.0 = SnailfishElement::Value(*val + add),
-            SnailfishElement::Pair(number) => number.add_left(add),
-        };
-    }
-
-    fn add_right(&mut self, add: usize) {
-        match &mut self.1 {
-            SnailfishElement::Value(val) => self.1 = SnailfishElement::Value(*val + add),
-            SnailfishElement::Pair(number) => number.add_right(add),
-        };
-    }
-
-    pub fn split(&mut self) -> bool {
-        log::trace!("split {:?}", self);
-        (match &mut self.0 {
-            SnailfishElement::Value(val) => {
-                if *val >= 10 {
-                    let left = val.checked_div(2).unwrap();
-                    let right = val.checked_sub(left).unwrap();
-                    self.0 = SnailfishElement::Pair(Box::new(Self(
-                        SnailfishElement::Value(left),
-                        SnailfishElement::Value(right),
-                    )));
-                    true
-                } else {
-                    false
-                }
+        Ok(number)
+    }
+
+    /// The pair of both numbers, before reduction
+    fn concat(self, other: Self) -> Self {
+        let mut regulars = self.0;
+        regulars.extend(other.0);
+        for regular in regulars.iter_mut() {
+            regular.depth += 1;
+        }
+        Self(regulars)
+    }
+
+    /// The unreduced sum, followed by the steps reducing it
+    pub fn add_steps(self, other: Self) -> (Self, ReductionSteps) {
+        let combined = self.concat(other);
+        (combined.clone(), ReductionSteps(combined))
+    }
+
+    /// Add and reduce, keeping track of every reduction action
+    pub fn add_traced(self, other: Self) -> (Self, Vec<ReductionStep>) {
+        let (combined, steps) = self.add_steps(other);
+        let steps: Vec<ReductionStep> = steps.collect();
+        let sum = steps.last().map_or(combined, |step| step.after.clone());
+        (sum, steps)
+    }
+
+    /// Path to the regular number at `idx`
+    fn path(&self, idx: usize) -> ElementPath {
+        // Walk the regular numbers in order, the path to the next one
+        // turns right at the last left turn and then keeps left
+        let mut path = Vec::new();
+        for (current, regular) in self.0.iter().enumerate() {
+            path.resize(regular.depth, Side::Left);
+            if current == idx {
+                break;
             }
-            SnailfishElement::Pair(number) => number.split(),
-        } || match &mut self.1 {
-            SnailfishElement::Value(val) => {
-                if *val >= 10 {
-                    let left = val.checked_div(2).unwrap();
-                    let right = val.checked_sub(left).unwrap();
-                    self.1 = SnailfishElement::Pair(Box::new(Self(
-                        SnailfishElement::Value(left),
-                        SnailfishElement::Value(right),
-                    )));
-                    true
-                } else {
-                    false
-                }
+            while path.last() == Some(&Side::Right) {
+                path.pop();
             }
-            SnailfishElement::Pair(number) => number.split(),
-        })
+            if let Some(side) = path.last_mut() {
+                *side = Side::Right;
+            }
+        }
+        ElementPath(path)
+    }
+
+    /// Apply the first applicable reduction action, if any
+    fn reduce_once(&mut self) -> Option<Reduction> {
+        self.explode().or_else(|| self.split())
+    }
+
+    /// Explode the leftmost pair of two regular numbers nested inside four pairs.
+    ///
+    /// A deeply nested regular number isn't always half of such a pair, the other
+    /// element can be a pair, but the deepest regular number always is.
+    pub fn explode(&mut self) -> Option<Reduction> {
+        let idx = (0..self.0.len().saturating_sub(1)).find(|&idx| {
+            let (left, right) = (self.0[idx], self.0[idx + 1]);
+            // Neighbours at the same depth are a pair when the first is a left element
+            left.depth > MAX_NESTING
+                && left.depth == right.depth
+                && self.path(idx).0.last() == Some(&Side::Left)
+        })?;
+        let Regular { value: left, depth } = self.0[idx];
+        let right = self.0[idx + 1].value;
+        let mut path = self.path(idx);
+        path.0.pop();
+        log::trace!("explode {} {:?}", idx, self);
+        // pair's left value is added to the first regular number to the left of the exploding pair
+        if let Some(regular) = idx.checked_sub(1).and_then(|prev| self.0.get_mut(prev)) {
+            regular.value += left;
+        }
+        // pair's right value is added to the first regular number to the right of the exploding pair
+        if let Some(regular) = self.0.get_mut(idx + 2) {
+            regular.value += right;
+        }
+        // the entire exploding pair is replaced with the regular number 0
+        self.0.splice(
+            idx..idx + 2,
+            [Regular {
+                value: 0,
+                depth: depth - 1,
+            }],
+        );
+        Some(Reduction::Explode { path, left, right })
+    }
+
+    /// Split the leftmost regular number of 10 or greater
+    pub fn split(&mut self) -> Option<Reduction> {
+        let idx = self.0.iter().position(|r| r.value >= 10)?;
+        let Regular { value, depth } = self.0[idx];
+        let path = self.path(idx);
+        log::trace!("split {} {:?}", idx, self);
+        let left = value / 2;
+        let right = value - left;
+        self.0.splice(
+            idx..=idx,
+            [
+                Regular {
+                    value: left,
+                    depth: depth + 1,
+                },
+                Regular {
+                    value: right,
+                    depth: depth + 1,
+                },
+            ],
+        );
+        Some(Reduction::Split { path, value })
     }
 
     // Pop pop
     pub fn magnitude(&self) -> usize {
-        3 * self.0.magnitude() + 2 * self.1.magnitude()
+        let mut stack: Vec<Regular> = Vec::with_capacity(MAX_NESTING + 2);
+        for regular in &self.0 {
+            stack.push(*regular);
+            // Collapse complete pairs, the right element is on top
+            while stack.len() >= 2 && stack[stack.len() - 1].depth == stack[stack.len() - 2].depth {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                stack.push(Regular {
+                    value: 3 * left.value + 2 * right.value,
+                    depth: left.depth - 1,
+                });
+            }
+        }
+        stack.pop().map_or(0, |regular| regular.value)
     }
 }
 
@@ -218,27 +387,35 @@ pub struct Homework(Vec<SnailfishNumber>);
 
 impl Homework {
     pub fn sum(&self) -> SnailfishNumber {
+        self.0.iter().sum()
+    }
+
+    fn max_magnitude_from(&self, left_idx: usize) -> usize {
+        let left = &self.0[left_idx];
         self.0
             .iter()
-            .cloned()
-            .reduce(|acc, next| acc + next)
-            .unwrap()
+            .enumerate()
+            .filter(|(right_idx, _)| *right_idx != left_idx)
+            .map(|(_, right)| (left + right).magnitude())
+            .max()
+            .unwrap_or(0)
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn max_pair_magnititude(&self) -> usize {
-        let mut max = 0;
-        for (left_idx, left) in self.0.iter().enumerate() {
-            for (right_idx, right) in self.0.iter().enumerate() {
-                if left_idx == right_idx {
-                    continue;
-                }
-                let magnitude = (left.clone() + right.clone()).magnitude();
-                if magnitude > max {
-                    max = magnitude;
-                }
-            }
-        }
-        max
+        (0..self.0.len())
+            .into_par_iter()
+            .map(|left_idx| self.max_magnitude_from(left_idx))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn max_pair_magnititude(&self) -> usize {
+        (0..self.0.len())
+            .map(|left_idx| self.max_magnitude_from(left_idx))
+            .max()
+            .unwrap_or(0)
     }
 }
 
@@ -345,43 +522,180 @@ mod tests {
         assert!(pair.is_err());
         let pair: ParseResult<SnailfishNumber> = "[[1,2],[[3,4],5]]]".parse();
         assert!(pair.is_err());
+        for bad in [
+            "",
+            "1",
+            "[1]",
+            "[1,2,3]",
+            "[1;2]",
+            "[[1,2]",
+            "[[[[[[1,2],3],4],5],6],7]",
+        ] {
+            let pair: ParseResult<SnailfishNumber> = bad.parse();
+            assert!(pair.is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_reduced() {
+        for number in DAY.example.lines() {
+            assert!(
+                SnailfishNumber::parse_reduced(number).is_ok(),
+                "{:?}",
+                number
+            );
+        }
+        for unreduced in [
+            "[[[[[1,2],[3,4]],0],0],0]",
+            "[[[[[9,8],1],2],3],4]",
+            "[10,1]",
+        ] {
+            assert!(
+                SnailfishNumber::parse_reduced(unreduced).is_err(),
+                "{:?}",
+                unreduced
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for number in DAY.example.lines().chain([
+            "[1,2]",
+            "[[1,2],3]",
+            "[10,[[3,4],25]]",
+            "[[[[[9,8],1],2],3],4]",
+        ]) {
+            let pair: SnailfishNumber = number.parse().unwrap();
+            assert_eq!(pair.to_string(), number);
+        }
+    }
+
+    #[test]
+    fn test_add_traced() {
+        let first: SnailfishNumber = "[[[[4,3],4],4],[7,[[8,4],9]]]".parse().unwrap();
+        let second: SnailfishNumber = "[1,1]".parse().unwrap();
+        let (sum, steps) = first.add_traced(second);
+        assert_eq!(sum.to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
+        let trace: Vec<(String, String)> = steps
+            .into_iter()
+            .map(|step| (step.reduction.to_string(), step.after.to_string()))
+            .collect();
+        assert_eq!(
+            trace,
+            vec![
+                (
+                    "explode [4,3] at LLLL".to_owned(),
+                    "[[[[0,7],4],[7,[[8,4],9]]],[1,1]]".to_owned()
+                ),
+                (
+                    "explode [8,4] at LRRL".to_owned(),
+                    "[[[[0,7],4],[15,[0,13]]],[1,1]]".to_owned()
+                ),
+                (
+                    "split 15 at LRL".to_owned(),
+                    "[[[[0,7],4],[[7,8],[0,13]]],[1,1]]".to_owned()
+                ),
+                (
+                    "split 13 at LRRR".to_owned(),
+                    "[[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]".to_owned()
+                ),
+                (
+                    "explode [6,7] at LRRR".to_owned(),
+                    "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_steps() {
+        let first: SnailfishNumber = "[[[[4,3],4],4],[7,[[8,4],9]]]".parse().unwrap();
+        let second: SnailfishNumber = "[1,1]".parse().unwrap();
+        let (combined, mut steps) = first.add_steps(second);
+        assert_eq!(
+            combined.to_string(),
+            "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]"
+        );
+        let step = steps.next().unwrap();
+        assert_eq!(step.before, combined);
+        assert_eq!(
+            step.reduction,
+            Reduction::Explode {
+                path: ElementPath(vec![Side::Left; 4]),
+                left: 4,
+                right: 3
+            }
+        );
+        let next = steps.next().unwrap();
+        assert_eq!(next.before, step.after);
+        assert_eq!(steps.count(), 3);
+        let (_combined, mut steps) = "[1,1]"
+            .parse::<SnailfishNumber>()
+            .unwrap()
+            .add_steps("[2,2]".parse().unwrap());
+        assert!(steps.next().is_none());
+    }
+
+    #[test]
+    fn test_path() {
+        let pair: SnailfishNumber = "[[1,[2,3]],4]".parse().unwrap();
+        let paths: Vec<String> = (0..4).map(|idx| pair.path(idx).to_string()).collect();
+        assert_eq!(paths, vec!["LL", "LRL", "LRR", "R"]);
     }
 
     #[test]
     fn test_explode_1() {
         let mut pair: SnailfishNumber = "[[[[[9,8],1],2],3],4]".parse().unwrap();
-        pair.explode(0);
+        pair.explode();
         assert_eq!(pair, "[[[[0,9],2],3],4]".parse().unwrap());
     }
 
     #[test]
     fn test_explode_2() {
         let mut pair: SnailfishNumber = "[7,[6,[5,[4,[3,2]]]]]".parse().unwrap();
-        pair.explode(0);
+        pair.explode();
         assert_eq!(pair, "[7,[6,[5,[7,0]]]]".parse().unwrap());
     }
 
     #[test]
     fn test_explode_3() {
         let mut pair: SnailfishNumber = "[[6,[5,[4,[3,2]]]],1]".parse().unwrap();
-        pair.explode(0);
+        pair.explode();
         assert_eq!(pair, "[[6,[5,[7,0]]],3]".parse().unwrap());
     }
 
     #[test]
     fn test_explode_4() {
         let mut pair: SnailfishNumber = "[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]".parse().unwrap();
-        pair.explode(0);
+        pair.explode();
         assert_eq!(pair, "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]".parse().unwrap());
     }
 
     #[test]
     fn test_explode_5() {
         let mut pair: SnailfishNumber = "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]".parse().unwrap();
-        pair.explode(0);
+        pair.explode();
         assert_eq!(pair, "[[3,[2,[8,0]]],[9,[5,[7,0]]]]".parse().unwrap());
     }
 
+    #[test]
+    fn test_explode_beside_pair() {
+        // [1,2] explodes first, leaving a 0 nested inside four pairs beside [3,4]
+        let left: SnailfishNumber = "[[[[[1,2],[3,4]],0],0],0]".parse().unwrap();
+        let right: SnailfishNumber = "[1,1]".parse().unwrap();
+        let mut pair = left.concat(right);
+        pair.explode();
+        assert_eq!(pair.to_string(), "[[[[[0,[5,4]],0],0],0],[1,1]]");
+        pair.explode();
+        assert_eq!(pair.to_string(), "[[[[[5,0],4],0],0],[1,1]]");
+        let left: SnailfishNumber = "[[[[[1,2],[3,4]],0],0],0]".parse().unwrap();
+        let right: SnailfishNumber = "[1,1]".parse().unwrap();
+        let sum = (left + right).to_string();
+        assert_eq!(sum, "[[[[0,4],0],0],[1,1]]");
+        assert_eq!(sum.parse::<SnailfishNumber>().unwrap().to_string(), sum);
+    }
+
     #[test]
     fn test_split() {
         let mut pair: SnailfishNumber = "[[[[0,7],4],[15,[0,13]]],[1,1]]".parse().unwrap();
@@ -411,6 +725,8 @@ mod tests {
             homework.sum(),
             "[[[[5,0],[7,4]],[5,5]],[6,6]]".parse().unwrap()
         );
+        let sum: SnailfishNumber = homework.0.into_iter().sum();
+        assert_eq!(sum.to_string(), "[[[[5,0],[7,4]],[5,5]],[6,6]]");
     }
 
     #[test]
@@ -1,9 +1,15 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 
 use crate::{Day, DayCalc, ParseError, ParseResult, PartOutput};
 
 const DIM: usize = 3;
+/// Overlapping scanners detect at least this many common beacons
+const OVERLAP_BEACONS: usize = 12;
+/// Pairwise distances between the common beacons
+const OVERLAP_DISTANCES: usize = OVERLAP_BEACONS * (OVERLAP_BEACONS - 1) / 2;
 
 // #[derive(Clone, Debug)]
 // pub struct Beacon([isize; DIM]);
@@ -17,7 +23,7 @@ type BeaconSet = BTreeSet<Beacon>;
 pub struct Scan {
     idx: usize,
     beacons: BeaconSet,
-    orientations: Vec<BeaconSet>,
+    fingerprint: BTreeMap<isize, usize>,
 }
 
 impl std::str::FromStr for Scan {
@@ -65,79 +71,279 @@ impl std::str::FromStr for Scan {
 
 impl Scan {
     fn new(idx: usize, beacons: BeaconSet) -> Self {
-        let mut orientations = Vec::new();
-        // I can throw half of these away, but I don't know which ones...
-        for x in 0..DIM {
-            for y in 0..DIM {
-                for z in 0..DIM {
-                    if x == y || y == z || x == z {
-                        continue;
-                    }
-                    for x_sig in [false, true] {
-                        for y_sig in [false, true] {
-                            for z_sig in [false, true] {
-                                orientations.push(
-                                    beacons
-                                        .iter()
-                                        .map(|p| {
-                                            let mut x = p[x];
-                                            if x_sig {
-                                                x = x.checked_neg().unwrap();
-                                            }
-                                            let mut y = p[y];
-                                            if y_sig {
-                                                y = y.checked_neg().unwrap();
-                                            }
-                                            let mut z = p[z];
-                                            if z_sig {
-                                                z = z.checked_neg().unwrap();
-                                            }
-                                            [x, y, z]
-                                        })
-                                        .collect(),
-                                );
-                            }
-                        }
+        let fingerprint = fingerprint(&beacons);
+        Scan {
+            idx,
+            beacons,
+            fingerprint,
+        }
+    }
+
+    /// Number of pairwise distances shared with another scan
+    fn shared_distances(&self, other: &Scan) -> usize {
+        self.fingerprint
+            .iter()
+            .map(|(distance, count)| {
+                other
+                    .fingerprint
+                    .get(distance)
+                    .map_or(0, |other_count| *count.min(other_count))
+            })
+            .sum()
+    }
+
+    /// Whether the fingerprints allow for enough common beacons to overlap
+    fn may_overlap(&self, other: &Scan) -> bool {
+        self.shared_distances(other) >= OVERLAP_DISTANCES
+    }
+
+    /// Find the placement of this scan relative to beacons in the global frame
+    fn align(&self, reference: &BeaconSet) -> Option<Placement> {
+        for orientation in Orientation::all() {
+            let rotated: Vec<Beacon> = self.beacons.iter().map(|b| orientation.apply(b)).collect();
+            let mut offsets: HashMap<Position, usize> = HashMap::new();
+            for reference_beacon in reference {
+                for beacon in &rotated {
+                    let offset = [
+                        reference_beacon[0] - beacon[0],
+                        reference_beacon[1] - beacon[1],
+                        reference_beacon[2] - beacon[2],
+                    ];
+                    let count = offsets.entry(offset).or_default();
+                    *count += 1;
+                    if *count >= OVERLAP_BEACONS {
+                        return Some(Placement {
+                            position: offset,
+                            orientation,
+                        });
                     }
                 }
             }
         }
-        Scan {
-            idx,
-            beacons,
-            orientations,
+        None
+    }
+}
+
+/// Multiset of squared distances between every pair of beacons,
+/// invariant under rotation and translation of the scan.
+fn fingerprint(beacons: &BeaconSet) -> BTreeMap<isize, usize> {
+    let mut fingerprint = BTreeMap::new();
+    for (idx, a) in beacons.iter().enumerate() {
+        for b in beacons.iter().skip(idx + 1) {
+            let distance = (0..DIM).map(|dim| (a[dim] - b[dim]).pow(2)).sum();
+            *fingerprint.entry(distance).or_default() += 1;
         }
     }
+    fingerprint
+}
+
+/// One of the 24 rotations a scanner can be facing.
+///
+/// Axis `dim` of the rotated position is taken from axis `axes[dim]` of the
+/// original, negated if `negate[dim]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Orientation {
+    axes: [usize; DIM],
+    negate: [bool; DIM],
+}
 
-    /// Get beasons relative to some reference
-    fn beacons_relative_to(&self, reference: &Beacon) -> BeaconSet {
-        beacons_relative_to(&self.beacons, reference)
+impl Default for Orientation {
+    fn default() -> Self {
+        Self {
+            axes: [0, 1, 2],
+            negate: [false; DIM],
+        }
     }
 }
 
-/// Get beasons relative to some reference
-fn beacons_relative_to(beacons: &BeaconSet, reference: &[isize; DIM]) -> BeaconSet {
-    beacons
-        .iter()
-        .filter(|b| *b != reference)
-        .map(|b| {
-            [
-                b[0] - reference[0],
-                b[1] - reference[1],
-                b[2] - reference[2],
-            ]
+impl Orientation {
+    /// All proper rotations, the identity first
+    pub fn all() -> impl Iterator<Item = Self> {
+        let permutations = [
+            ([0, 1, 2], false),
+            ([1, 2, 0], false),
+            ([2, 0, 1], false),
+            ([0, 2, 1], true),
+            ([2, 1, 0], true),
+            ([1, 0, 2], true),
+        ];
+        permutations.into_iter().flat_map(|(axes, odd)| {
+            (0..1 << DIM).filter_map(move |signs: usize| {
+                let negate = [signs & 1 != 0, signs & 2 != 0, signs & 4 != 0];
+                // Odd permutations need an odd number of negations to not mirror
+                if (signs.count_ones() % 2 == 1) == odd {
+                    Some(Self { axes, negate })
+                } else {
+                    None
+                }
+            })
         })
-        .collect()
+    }
+
+    /// Rotation matrix, such that `apply` is `matrix * position`
+    pub fn matrix(&self) -> [[isize; DIM]; DIM] {
+        let mut matrix = [[0; DIM]; DIM];
+        for dim in 0..DIM {
+            matrix[dim][self.axes[dim]] = if self.negate[dim] { -1 } else { 1 };
+        }
+        matrix
+    }
+
+    pub fn apply(&self, position: &Position) -> Position {
+        let mut rotated = [0; DIM];
+        for dim in 0..DIM {
+            rotated[dim] = position[self.axes[dim]];
+            if self.negate[dim] {
+                rotated[dim] = rotated[dim].checked_neg().unwrap();
+            }
+        }
+        rotated
+    }
+}
+
+/// Where a scanner sits in the frame of the first scanner
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Placement {
+    pub position: Scanner,
+    pub orientation: Orientation,
+}
+
+impl Placement {
+    fn apply(&self, beacon: &Beacon) -> Beacon {
+        let rotated = self.orientation.apply(beacon);
+        [
+            rotated[0] + self.position[0],
+            rotated[1] + self.position[1],
+            rotated[2] + self.position[2],
+        ]
+    }
+}
+
+/// A JSON array of numbers, or of arrays already written as JSON
+fn json_array<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_string()).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// The reconstructed map, in the frame of the first scanner
+#[derive(Clone, Debug)]
+pub struct BeaconMap {
+    /// Placement of each scanner, by scanner number
+    pub placements: BTreeMap<usize, Placement>,
+    pub beacons: BeaconSet,
+    /// Scanners detecting each beacon
+    pub origins: BTreeMap<Beacon, BTreeSet<usize>>,
+    /// Pairs of scanners detecting at least 12 common beacons
+    pub overlaps: Vec<(usize, usize)>,
+}
+
+impl BeaconMap {
+    pub fn scanners(&self) -> Vec<Scanner> {
+        self.placements
+            .values()
+            .map(|placement| placement.position)
+            .collect()
+    }
+
+    /// Export as a JSON document of scanners, beacons and overlaps
+    pub fn to_json(&self) -> String {
+        let scanners: Vec<String> = self
+            .placements
+            .iter()
+            .map(|(idx, placement)| {
+                let rotation = placement.orientation.matrix().map(json_array);
+                format!(
+                    "{{\"scanner\":{},\"position\":{},\"rotation\":{}}}",
+                    idx,
+                    json_array(placement.position),
+                    json_array(rotation)
+                )
+            })
+            .collect();
+        let beacons: Vec<String> = self
+            .origins
+            .iter()
+            .map(|(beacon, scanners)| {
+                format!(
+                    "{{\"position\":{},\"scanners\":{}}}",
+                    json_array(beacon),
+                    json_array(scanners)
+                )
+            })
+            .collect();
+        let overlaps: Vec<String> = self
+            .overlaps
+            .iter()
+            .map(|(a, b)| format!("[{},{}]", a, b))
+            .collect();
+        format!(
+            "{{\"scanners\":[{}],\"beacons\":[{}],\"overlaps\":[{}]}}",
+            scanners.join(","),
+            beacons.join(","),
+            overlaps.join(",")
+        )
+    }
+
+    /// Coloured points: scanners in red, beacons in white
+    fn points(&self) -> impl Iterator<Item = (Position, [u8; 3])> + '_ {
+        self.scanners()
+            .into_iter()
+            .map(|scanner| (scanner, [255, 0, 0]))
+            .chain(self.beacons.iter().map(|beacon| (*beacon, [255, 255, 255])))
+    }
+
+    /// Export as an ASCII PLY point cloud
+    pub fn to_ply(&self) -> String {
+        let mut ply = format!(
+            "ply\nformat ascii 1.0\ncomment advent2021 day19 beacon map\nelement vertex {}\n",
+            self.placements.len() + self.beacons.len()
+        );
+        ply.push_str("property int x\nproperty int y\nproperty int z\n");
+        ply.push_str("property uchar red\nproperty uchar green\nproperty uchar blue\n");
+        ply.push_str("end_header\n");
+        for (point, colour) in self.points() {
+            ply.push_str(&format!(
+                "{} {} {} {} {} {}\n",
+                point[0], point[1], point[2], colour[0], colour[1], colour[2]
+            ));
+        }
+        ply
+    }
+
+    /// Export as OBJ vertices, with the common vertex colour extension
+    pub fn to_obj(&self) -> String {
+        let mut obj = "# advent2021 day19 beacon map\n".to_owned();
+        for (point, colour) in self.points() {
+            obj.push_str(&format!(
+                "v {} {} {} {} {} {}\n",
+                point[0],
+                point[1],
+                point[2],
+                f64::from(colour[0]) / 255.0,
+                f64::from(colour[1]) / 255.0,
+                f64::from(colour[2]) / 255.0
+            ));
+        }
+        obj
+    }
 }
 
-/// Some sample of the beacons to check
-fn beacons_sample(beacons: &BeaconSet) -> BeaconSet {
-    let mut edges = BeaconSet::new();
-    for dim in 0..DIM {
-        edges.insert(*beacons.iter().max_by_key(|beacon| beacon[dim]).unwrap());
-        edges.insert(*beacons.iter().min_by_key(|beacon| beacon[dim]).unwrap());
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapError {
+    Empty,
+    /// The listed scanners share no overlap with the rest
+    Disconnected(Vec<usize>),
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no scanners to map"),
+            Self::Disconnected(unaligned) => {
+                write!(f, "scanners {:?} cannot be aligned", unaligned)
+            }
+        }
     }
-    edges
 }
 
 #[derive(Clone, Debug)]
@@ -157,93 +363,67 @@ impl std::str::FromStr for Report {
 }
 
 impl Report {
-    pub fn map(&self) -> (Vec<Scanner>, BeaconSet) {
-        let first_scan = self.scans.first().unwrap();
-        let mut beacons: BeaconSet = first_scan.beacons.clone();
-        log::trace!("beacons: {:?}", beacons);
-        let mut reference_beacons: BTreeMap<Beacon, BeaconSet> = beacons
+    /// Align every scan to the first one,
+    /// only trying rotations for scans whose fingerprints suggest an overlap.
+    pub fn map(&self) -> Result<BeaconMap, MapError> {
+        let first_scan = self.scans.first().ok_or(MapError::Empty)?;
+        let mut placements = BTreeMap::from([(first_scan.idx, Placement::default())]);
+        let mut aligned: BTreeMap<usize, BeaconSet> =
+            BTreeMap::from([(first_scan.idx, first_scan.beacons.clone())]);
+        let mut queue = VecDeque::from([first_scan]);
+        while let Some(reference) = queue.pop_front() {
+            let reference_beacons = aligned.get(&reference.idx).unwrap().clone();
+            for scan in &self.scans {
+                if placements.contains_key(&scan.idx) || !reference.may_overlap(scan) {
+                    continue;
+                }
+                log::debug!("candidate: scan {} to scan {}", scan.idx, reference.idx);
+                if let Some(placement) = scan.align(&reference_beacons) {
+                    log::debug!("match! scan {}: {:?}", scan.idx, placement);
+                    placements.insert(scan.idx, placement);
+                    aligned.insert(
+                        scan.idx,
+                        scan.beacons.iter().map(|b| placement.apply(b)).collect(),
+                    );
+                    queue.push_back(scan);
+                }
+            }
+        }
+        let unaligned: Vec<usize> = self
+            .scans
             .iter()
-            .cloned()
-            .map(|beacon| (beacon, first_scan.beacons_relative_to(&beacon)))
+            .map(|scan| scan.idx)
+            .filter(|idx| !placements.contains_key(idx))
             .collect();
-        let mut edge_beacons = beacons_sample(&beacons);
-        let mut prev_scans_seen = Vec::new();
-        let mut scans_seen = vec![first_scan.idx];
-        let mut scanners = vec![[0, 0, 0]];
-        while scans_seen.len() < self.scans.len() {
-            if prev_scans_seen == scans_seen {
-                log::warn!("dead end!");
-            }
-            prev_scans_seen = scans_seen.clone();
-            'scan: for scan in self
-                .scans
-                .iter()
-                .filter(|s| !prev_scans_seen.contains(&s.idx))
-            {
-                let orientations = &scan.orientations;
-                log::debug!(
-                    "scan: {} - orientations: {} - reference_beacons: {}",
-                    scan.idx,
-                    orientations.len(),
-                    reference_beacons.len()
-                );
-                log::trace!("scan: {:?}", scan);
-                for orientation_beacons in orientations {
-                    log::trace!("orientation_beacons: {:?}", orientation_beacons);
-                    let heuristic_beacons = if prev_scans_seen == scans_seen {
-                        &beacons
-                    } else {
-                        &edge_beacons
-                    };
-                    for reference_beacon in heuristic_beacons {
-                        let reference_relatives = reference_beacons.get(reference_beacon).unwrap();
-                        log::trace!("reference_relatives: {:?}", reference_relatives);
-                        for beacon in orientation_beacons {
-                            let relatives = beacons_relative_to(orientation_beacons, beacon);
-                            log::trace!("relatives: {:?}", relatives);
-                            let intersection: BeaconSet = relatives
-                                .intersection(reference_relatives)
-                                .cloned()
-                                .collect();
-                            log::trace!("intersection: {:?}", intersection);
-                            if intersection.len() >= 11 {
-                                // We count the centre beacon
-                                log::debug!("match! scan {}", scan.idx);
-                                let scanner = [
-                                    reference_beacon[0] - beacon[0],
-                                    reference_beacon[1] - beacon[1],
-                                    reference_beacon[2] - beacon[2],
-                                ];
-                                log::debug!("scanner: {:?}", scanner);
-                                scanners.push(scanner);
-                                let remapped_beacons: BeaconSet = relatives
-                                    .into_iter()
-                                    .map(|b| {
-                                        [
-                                            reference_beacon[0] + b[0],
-                                            reference_beacon[1] + b[1],
-                                            reference_beacon[2] + b[2],
-                                        ]
-                                    })
-                                    .collect();
-                                beacons.extend(remapped_beacons);
-                                log::trace!("beacons: {:?}", beacons);
-                                reference_beacons = beacons
-                                    .iter()
-                                    .cloned()
-                                    .map(|beacon| (beacon, beacons_relative_to(&beacons, &beacon)))
-                                    .collect();
-                                edge_beacons = beacons_sample(&beacons);
-                                scans_seen.push(scan.idx);
-                                continue 'scan;
-                            }
-                        }
-                    }
+        if !unaligned.is_empty() {
+            return Err(MapError::Disconnected(unaligned));
+        }
+        let mut overlaps = Vec::new();
+        for (a_idx, a_scan) in self.scans.iter().enumerate() {
+            for b_scan in self.scans.iter().skip(a_idx + 1) {
+                if a_scan.may_overlap(b_scan)
+                    && aligned[&a_scan.idx]
+                        .intersection(&aligned[&b_scan.idx])
+                        .count()
+                        >= OVERLAP_BEACONS
+                {
+                    overlaps.push((a_scan.idx, b_scan.idx));
                 }
-                log::info!("no match! scan {}", scan.idx);
             }
         }
-        (scanners, beacons)
+        let mut origins: BTreeMap<Beacon, BTreeSet<usize>> = BTreeMap::new();
+        for (idx, beacons) in aligned {
+            for beacon in beacons {
+                origins.entry(beacon).or_default().insert(idx);
+            }
+        }
+        let beacons = origins.keys().cloned().collect();
+        Ok(BeaconMap {
+            placements,
+            beacons,
+            origins,
+            overlaps,
+        })
     }
 }
 
@@ -252,14 +432,14 @@ pub fn parse(input: &str) -> ParseResult<Report> {
 }
 
 pub fn part1(report: &Report) -> PartOutput<usize> {
-    let (_scanners, beacons) = report.map();
+    let map = report.map().expect("scanners cannot be aligned");
     PartOutput {
-        answer: beacons.len(),
+        answer: map.beacons.len(),
     }
 }
 
 pub fn part2(report: &Report) -> PartOutput<usize> {
-    let (scanners, _beacons) = report.map();
+    let scanners = report.map().expect("scanners cannot be aligned").scanners();
     let mut max = 0;
     for a_scanner in &scanners {
         for b_scanner in &scanners {
@@ -293,24 +473,102 @@ pub const DAY: Day<Report, usize> = Day {
 #[cfg(test)]
 mod tests {
     use super::*;
-    // use crate::get_input;
+    use crate::get_input;
     use test_log::test;
 
     #[test]
     fn test_scan() {
         let scan: Scan = "--- scanner 0 ---\n-1,-1,1\n8,0,7".parse().unwrap();
         log::debug!("scan: {:?}", scan);
-        log::debug!("scan.orientations: {:#?}", scan.orientations)
+        log::debug!("scan.fingerprint: {:#?}", scan.fingerprint);
+        assert_eq!(scan.fingerprint, BTreeMap::from([(81 + 1 + 36, 1)]));
+    }
+
+    #[test]
+    fn test_orientations() {
+        let rotated: BTreeSet<Position> = Orientation::all()
+            .map(|orientation| orientation.apply(&[1, 2, 3]))
+            .collect();
+        assert_eq!(rotated.len(), 24);
+        assert_eq!(Orientation::all().next(), Some(Orientation::default()));
+        for orientation in Orientation::all() {
+            let matrix = orientation.matrix();
+            let position = [1, 2, 3];
+            let product: Vec<isize> = matrix
+                .iter()
+                .map(|row| (0..DIM).map(|dim| row[dim] * position[dim]).sum())
+                .collect();
+            assert_eq!(product, orientation.apply(&position));
+        }
+        // Mirror image is not a rotation
+        assert!(!rotated.contains(&[-1, 2, 3]));
     }
 
     #[test]
     fn test_example_report() {
         let report = parse(DAY.example).unwrap();
         log::debug!("report: {:?}", report);
-        let (_scanners, beacons) = report.map();
-        log::debug!("beacons: {:?}", beacons);
-        log::debug!("beacons.len(): {}", beacons.len());
-        assert_eq!(beacons.len(), 79)
+        let map = report.map().unwrap();
+        log::debug!("beacons: {:?}", map.beacons);
+        log::debug!("beacons.len(): {}", map.beacons.len());
+        assert_eq!(map.beacons.len(), 79);
+        assert_eq!(
+            map.scanners(),
+            vec![
+                [0, 0, 0],
+                [68, -1246, -43],
+                [1105, -1205, 1229],
+                [-92, -2380, -20],
+                [-20, -1133, 1061]
+            ]
+        );
+        assert_eq!(map.overlaps, vec![(0, 1), (1, 3), (1, 4), (2, 4)]);
+    }
+
+    #[test]
+    fn test_example_export() {
+        let report = parse(DAY.example).unwrap();
+        let map = report.map().unwrap();
+        assert_eq!(map.origins.len(), 79);
+        assert_eq!(
+            map.origins.get(&[459, -707, 401]),
+            Some(&BTreeSet::from([0, 1, 2, 4]))
+        );
+        let json = map.to_json();
+        assert!(json.starts_with(
+            "{\"scanners\":[{\"scanner\":0,\"position\":[0,0,0],\"rotation\":[[1,0,0],[0,1,0],[0,0,1]]},"
+        ));
+        assert!(json.contains("{\"position\":[459,-707,401],\"scanners\":[0,1,2,4]}"));
+        assert!(json.ends_with("\"overlaps\":[[0,1],[1,3],[1,4],[2,4]]}"));
+        let ply = map.to_ply();
+        assert!(ply.contains("element vertex 84\n"));
+        assert!(ply.contains("\n68 -1246 -43 255 0 0\n"));
+        assert_eq!(ply.lines().count(), 11 + 84);
+        let obj = map.to_obj();
+        assert!(obj.contains("\nv 68 -1246 -43 1 0 0\n"));
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("v ")).count(),
+            84
+        );
+    }
+
+    #[test]
+    fn test_disconnected() {
+        let report: Report = DAY
+            .example
+            .split("\n\n")
+            .take(3)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+            .parse()
+            .unwrap();
+        assert_eq!(report.map().unwrap_err(), MapError::Disconnected(vec![2]));
+    }
+
+    #[test]
+    fn test_example_part2() {
+        let report = parse(DAY.example).unwrap();
+        assert_eq!(part2(&report).answer, 3621);
     }
 
     // #[test]
@@ -320,17 +578,10 @@ mod tests {
     //     assert_eq!(result, -1);
     // }
 
-    // #[test]
-    // fn test_example_part2() {
-    //     let something = parse(DAY.example).unwrap();
-    //     let result = play(&something);
-    //     assert_eq!(result, -1);
-    // }
-
-    // #[test]
-    // fn test_main() {
-    //     let something = parse(&get_input(0)).unwrap();
-    //     assert_eq!(part1(&something).answer.to_string(), "-1");
-    //     assert_eq!(part2(&something).answer.to_string(), "-1");
-    // }
+    #[test]
+    fn test_main() {
+        let report = parse(&get_input(19)).unwrap();
+        assert_eq!(part1(&report).answer.to_string(), "414");
+        assert_eq!(part2(&report).answer.to_string(), "13000");
+    }
 }
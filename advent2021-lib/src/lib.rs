@@ -24,6 +24,11 @@ pub mod day15;
 pub mod day17;
 pub mod day18;
 pub mod day19;
+pub mod matrix;
+pub mod ocr;
+pub mod raster;
+#[cfg(test)]
+mod test_rng;
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
@@ -0,0 +1,158 @@
+/// Numbers that square matrices can count with
+pub trait Count: Clone + num_traits::Zero + num_traits::One {}
+
+impl<T: Clone + num_traits::Zero + num_traits::One> Count for T {}
+
+/// A count that is `None` once it no longer fits in a `u64`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checked(pub Option<u64>);
+
+impl std::ops::Add for Checked {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self(self.0.zip(other.0).and_then(|(a, b)| a.checked_add(b)))
+    }
+}
+
+impl std::ops::Mul for Checked {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            // However big the other count, the product is still zero
+            (Some(0), _) | (_, Some(0)) => Self(Some(0)),
+            (Some(a), Some(b)) => Self(a.checked_mul(b)),
+            _ => Self(None),
+        }
+    }
+}
+
+impl num_traits::Zero for Checked {
+    fn zero() -> Self {
+        Self(Some(0))
+    }
+    fn is_zero(&self) -> bool {
+        self.0 == Some(0)
+    }
+}
+
+impl num_traits::One for Checked {
+    fn one() -> Self {
+        Self(Some(1))
+    }
+}
+
+/// A square matrix, indexed by `(row, column)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix<T>(Vec<Vec<T>>);
+
+impl<T: Count> Matrix<T> {
+    pub fn zeros(size: usize) -> Self {
+        Self(vec![vec![T::zero(); size]; size])
+    }
+
+    pub fn identity(size: usize) -> Self {
+        Self(
+            (0..size)
+                .map(|row| {
+                    (0..size)
+                        .map(|col| if row == col { T::one() } else { T::zero() })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let size = self.0.len();
+        Self(
+            (0..size)
+                .map(|row| {
+                    (0..size)
+                        .map(|col| {
+                            (0..size).fold(T::zero(), |acc, k| {
+                                acc + self.0[row][k].clone() * other.0[k][col].clone()
+                            })
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Raises the matrix to the power `n` by repeated squaring
+    pub fn pow(&self, mut n: u64) -> Self {
+        let mut result = Self::identity(self.0.len());
+        let mut square = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(&square);
+            }
+            n >>= 1;
+            if n > 0 {
+                square = square.mul(&square);
+            }
+        }
+        result
+    }
+
+    pub fn apply(&self, vector: &[T]) -> Vec<T> {
+        self.0
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(vector)
+                    .fold(T::zero(), |acc, (a, b)| acc + a.clone() * b.clone())
+            })
+            .collect()
+    }
+}
+
+impl<T> std::ops::Index<(usize, usize)> for Matrix<T> {
+    type Output = T;
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        &self.0[row][col]
+    }
+}
+
+impl<T> std::ops::IndexMut<(usize, usize)> for Matrix<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        &mut self.0[row][col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_pow() {
+        // Fibonacci numbers
+        let mut matrix: Matrix<u64> = Matrix::zeros(2);
+        matrix[(0, 0)] = 1;
+        matrix[(0, 1)] = 1;
+        matrix[(1, 0)] = 1;
+        assert_eq!(matrix.pow(0), Matrix::identity(2));
+        assert_eq!(matrix.pow(10)[(0, 1)], 55);
+        assert_eq!(
+            matrix.pow(90).apply(&[1, 0]),
+            [4660046610375530309, 2880067194370816120]
+        );
+    }
+
+    #[test]
+    fn test_checked() {
+        let mut matrix: Matrix<Checked> = Matrix::zeros(2);
+        matrix[(0, 0)] = Checked(Some(1));
+        matrix[(0, 1)] = Checked(Some(1));
+        matrix[(1, 0)] = Checked(Some(1));
+        assert_eq!(matrix.pow(93)[(0, 1)], Checked(Some(12200160415121876738)));
+        assert_eq!(matrix.pow(94)[(0, 1)], Checked(None));
+        assert_eq!(Checked(None) * Checked(Some(0)), Checked(Some(0)));
+        assert_eq!(Checked(None) + Checked(Some(0)), Checked(None));
+    }
+}
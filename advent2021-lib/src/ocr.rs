@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+/// Glyphs are separated by a single blank column
+pub const GLYPH_PITCH: usize = GLYPH_WIDTH + 1;
+
+/// The capital letters of the puzzles' dot matrix font, one row per line
+const FONT: [(char, &str); 18] = [
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...\n#...\n.#.#\n..#.\n..#.\n..#."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+/// Draws the points as rows of `#` and `.`, from the origin to the furthest point
+pub fn render(points: &HashSet<(usize, usize)>) -> String {
+    let max_x = points.iter().map(|pos| pos.0).max().unwrap_or(0);
+    let max_y = points.iter().map(|pos| pos.1).max().unwrap_or(0);
+    (0..=max_y)
+        .map(|y| {
+            (0..=max_x)
+                .map(|x| if points.contains(&(x, y)) { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn glyph(points: &HashSet<(usize, usize)>, idx: usize) -> String {
+    let left = idx * GLYPH_PITCH;
+    (0..GLYPH_HEIGHT)
+        .map(|y| {
+            (left..left + GLYPH_WIDTH)
+                .map(|x| if points.contains(&(x, y)) { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Reads the letters from points that start at the origin, `None` if any glyph is unknown
+pub fn recognise(points: &HashSet<(usize, usize)>) -> Option<String> {
+    let max_x = points.iter().map(|pos| pos.0).max()?;
+    if points.iter().any(|pos| pos.1 >= GLYPH_HEIGHT) {
+        return None;
+    }
+    (0..=max_x / GLYPH_PITCH)
+        .map(|idx| {
+            let glyph = glyph(points, idx);
+            FONT.iter()
+                .find(|(_, font)| *font == glyph)
+                .map(|(letter, _)| *letter)
+        })
+        .collect()
+}
+
+/// The letters if they can all be recognised, otherwise the rendered points
+pub fn read(points: &HashSet<(usize, usize)>) -> String {
+    recognise(points).unwrap_or_else(|| {
+        log::warn!("unrecognised glyphs:\n{}", render(points));
+        format!("\n{}", render(points))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn points(rendered: &str) -> HashSet<(usize, usize)> {
+        rendered
+            .lines()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == '#')
+                    .map(move |(x, _)| (x, y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_font() {
+        let rows: Vec<String> = (0..GLYPH_HEIGHT)
+            .map(|y| {
+                FONT.iter()
+                    .map(|(_, font)| font.lines().nth(y).unwrap())
+                    .collect::<Vec<&str>>()
+                    .join(".")
+            })
+            .collect();
+        let alphabet = points(&rows.join("\n"));
+        assert_eq!(recognise(&alphabet).as_deref(), Some("ABCEFGHIJKLOPRSUYZ"));
+        assert_eq!(render(&alphabet), rows.join("\n"));
+    }
+
+    #[test]
+    fn test_unknown_glyph() {
+        let square = points("#####\n#...#\n#...#\n#...#\n#...#\n#####");
+        assert_eq!(recognise(&square), None);
+        assert_eq!(read(&square), format!("\n{}", render(&square)));
+    }
+}
@@ -0,0 +1,136 @@
+/// A pixel as red, green and blue
+pub type Rgb = [u8; 3];
+
+/// A binary PPM, with `pixels` in rows from the top left
+pub fn to_ppm(width: usize, height: usize, pixels: &[Rgb]) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    ppm.extend(pixels.iter().flatten());
+    ppm
+}
+
+/// A truecolour PNG, with `pixels` in rows from the top left. The image data
+/// goes in uncompressed deflate blocks, so the file is about as big as a PPM.
+pub fn to_png(width: usize, height: usize, pixels: &[Rgb]) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = vec![];
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // 8 bit RGB, deflate, adaptive filtering, no interlace
+    header.extend([8, 2, 0, 0, 0]);
+    png_chunk(&mut png, b"IHDR", &header);
+    let mut scanlines = Vec::with_capacity(height * (1 + width * 3));
+    for row in pixels.chunks(width.max(1)) {
+        // No filter
+        scanlines.push(0);
+        scanlines.extend(row.iter().flatten());
+    }
+    png_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Table for the CRC-32 used by PNG and zip, one entry per byte value
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+        let a = (a + byte as u32) % MOD;
+        (a, (b + a) % MOD)
+    });
+    (b << 16) | a
+}
+
+/// A zlib stream holding the data in stored, uncompressed, deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+    // Deflate with a 32K window and no preset dictionary, a multiple of 31 as required
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks: Vec<&[u8]> = data.chunks(MAX_BLOCK).collect();
+    if blocks.is_empty() {
+        blocks.push(&[]);
+    }
+    let last = blocks.len() - 1;
+    for (idx, block) in blocks.into_iter().enumerate() {
+        // The final block sets the lowest bit
+        zlib.push(u8::from(idx == last));
+        let len = block.len() as u16;
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend(block);
+    }
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_zlib_stored_blocks() {
+        let data: Vec<u8> = (0..70_000).map(|idx| idx as u8).collect();
+        let zlib = zlib_stored(&data);
+        assert_eq!(zlib.len(), 2 + 2 * 5 + data.len() + 4);
+        assert_eq!(&zlib[2..7], &[0, 0xff, 0xff, 0, 0]);
+        assert_eq!(zlib[2 + 5 + 0xffff], 1);
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_png_layout() {
+        let png = to_png(2, 1, &[[255, 0, 0], [0, 0, 255]]);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+        assert_eq!(
+            to_ppm(2, 1, &[[255, 0, 0], [0, 0, 255]]),
+            b"P6\n2 1\n255\n\xff\0\0\0\0\xff"
+        );
+    }
+}
@@ -0,0 +1,17 @@
+/// Small xorshift generator so the property tests are reproducible
+pub struct Rng(u64);
+
+impl Rng {
+    /// The seed must not be zero, or every number is zero
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// The next number below `max`
+    pub fn below(&mut self, max: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % max
+    }
+}
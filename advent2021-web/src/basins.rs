@@ -0,0 +1,76 @@
+use yew::prelude::*;
+use yew::virtual_dom::VNode;
+
+use advent2021_lib::day09::{self, Basin};
+
+pub enum Msg {
+    Heightmap(String),
+}
+
+/// Panel to label the basins of a heightmap and draw them
+pub struct Basins {
+    // `ComponentLink` is like a reference to a component.
+    // It can be used to send messages to the component
+    link: ComponentLink<Self>,
+    heightmap: String,
+}
+
+impl Basins {
+    fn view_basins(&self) -> Html {
+        let heightmap = match day09::parse(&self.heightmap) {
+            Ok(heightmap) => heightmap,
+            Err(_e) => return html! { <p>{"Parsing error, please try again..."}</p> },
+        };
+        let mut sizes: Vec<usize> = heightmap.basins().iter().map(Basin::size).collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        let div = yew::utils::document().create_element("div").unwrap();
+        div.set_inner_html(&heightmap.render_svg());
+        html! {
+            <>
+                <p>{format!("{} basins, sized {:?}", sizes.len(), sizes)}</p>
+                <div class="basins">{VNode::VRef(div.into())}</div>
+            </>
+        }
+    }
+}
+
+impl Component for Basins {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            heightmap: day09::DAY.example.to_owned(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Heightmap(heightmap) => self.heightmap = heightmap,
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        // This component has no properties so we will always return "false".
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <section class="day-even">
+                <div class="row">
+                    <div class="row-item day-key"><h4>{"Day 9:"}</h4></div>
+                    <div class="row-item day-title"><h2><em>{"Smoke Basin"}</em></h2></div>
+                </div>
+                <div class="row">
+                    <div class="row-item day-title">
+                        <textarea class="heightmap-input" rows="5" value=self.heightmap.clone() oninput=self.link.callback(|e: InputData| Msg::Heightmap(e.value)) />
+                    </div>
+                </div>
+                { self.view_basins() }
+            </section>
+        }
+    }
+}
@@ -2,6 +2,10 @@ use yew::prelude::*;
 
 use advent2021_lib::get_days;
 
+mod basins;
+mod origami;
+mod segments;
+mod snailfish;
 mod web;
 
 // Use `wee_alloc` as the global allocator.
@@ -54,6 +58,10 @@ impl Component for Model {
                         }
                     })
                 }
+                <segments::Segments />
+                <basins::Basins />
+                <origami::Origami />
+                <snailfish::Snailfish />
             </div>
         }
     }
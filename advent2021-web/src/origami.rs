@@ -0,0 +1,138 @@
+use yew::prelude::*;
+use yew::virtual_dom::VNode;
+
+use advent2021_lib::day13::{self, Fold, Paper};
+
+pub enum Msg {
+    Instructions(String),
+    Previous,
+    Next,
+}
+
+/// Panel to fold transparent paper one instruction at a time
+pub struct Origami {
+    // `ComponentLink` is like a reference to a component.
+    // It can be used to send messages to the component
+    link: ComponentLink<Self>,
+    instructions: String,
+    folds: Vec<Fold>,
+    /// The unfolded paper followed by the paper after each fold
+    papers: Vec<Paper>,
+    /// Number of folds shown
+    current: usize,
+    error: Option<String>,
+}
+
+impl Origami {
+    fn fold(&mut self) {
+        self.current = 0;
+        self.papers = Vec::new();
+        self.folds = Vec::new();
+        self.error = None;
+        let instructions = match self.instructions.parse::<day13::TransparentPaper>() {
+            Ok(instructions) => instructions,
+            Err(_e) => {
+                self.error = Some("Parsing error, please try again...".to_owned());
+                return;
+            }
+        };
+        self.papers.push(instructions.paper());
+        for folded in instructions.fold_steps() {
+            match folded {
+                Ok(folded) => self.papers.push(folded),
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+        self.folds = instructions.folds().to_vec();
+    }
+
+    fn view_paper(&self) -> Html {
+        let paper = match self.papers.get(self.current) {
+            Some(paper) => paper,
+            None => return html! {},
+        };
+        let (width, height) = paper.dimensions();
+        let div = yew::utils::document().create_element("div").unwrap();
+        div.set_inner_html(&paper.render_svg(400, self.folds.get(self.current)));
+        let last = self.papers.len() - 1;
+        html! {
+            <>
+                <div class="row">
+                    <div class="row-item">
+                        <button type="button" disabled={self.current == 0} onclick=self.link.callback(|_| Msg::Previous)>{ "◀ Unfold" }</button>
+                        <button type="button" disabled={self.current == last} onclick=self.link.callback(|_| Msg::Next)>{ "Fold ▶" }</button>
+                    </div>
+                    <div class="row-item">
+                        <h5>{format!("Fold {} / {}", self.current, self.folds.len())}</h5>
+                    </div>
+                </div>
+                <p>{format!("{}x{} with {} dots", width, height, paper.dots().len())}</p>
+                {
+                    match self.folds.get(self.current) {
+                        Some(fold) if self.current < last => html! { <p>{format!("Next: {}", fold)}</p> },
+                        _ => html! {},
+                    }
+                }
+                <div class="origami">{VNode::VRef(div.into())}</div>
+            </>
+        }
+    }
+}
+
+impl Component for Origami {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut origami = Self {
+            link,
+            instructions: day13::DAY.example.to_owned(),
+            folds: Vec::new(),
+            papers: Vec::new(),
+            current: 0,
+            error: None,
+        };
+        origami.fold();
+        origami
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Instructions(instructions) => {
+                self.instructions = instructions;
+                self.fold();
+            }
+            Msg::Previous => self.current = self.current.saturating_sub(1),
+            Msg::Next => self.current = (self.current + 1).min(self.papers.len().saturating_sub(1)),
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        // This component has no properties so we will always return "false".
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <section class="day-odd">
+                <div class="row">
+                    <div class="row-item day-key"><h4>{"Day 13:"}</h4></div>
+                    <div class="row-item day-title"><h2><em>{"Transparent Origami"}</em></h2></div>
+                </div>
+                <div class="row">
+                    <div class="row-item day-title">
+                        <textarea class="origami-input" rows="5" value=self.instructions.clone() oninput=self.link.callback(|e: InputData| Msg::Instructions(e.value)) />
+                    </div>
+                </div>
+                { self.view_paper() }
+                {
+                    match &self.error {
+                        Some(error) => html! { <p>{error}</p> },
+                        None => html! {},
+                    }
+                }
+            </section>
+        }
+    }
+}
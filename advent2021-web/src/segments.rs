@@ -0,0 +1,93 @@
+use yew::prelude::*;
+use yew::virtual_dom::VNode;
+
+use advent2021_lib::day08::DisplayObservation;
+
+pub enum Msg {
+    Display(String),
+    Highlight,
+}
+
+/// Panel to decode a scrambled display and draw its output digits
+pub struct Segments {
+    // `ComponentLink` is like a reference to a component.
+    // It can be used to send messages to the component
+    link: ComponentLink<Self>,
+    display: String,
+    highlight_wires: bool,
+}
+
+impl Segments {
+    fn view_display(&self) -> Html {
+        let display: DisplayObservation = match self.display.parse() {
+            Ok(display) => display,
+            Err(_e) => return html! { <p>{"Parsing error, please try again..."}</p> },
+        };
+        match (display.solve(), display.render_svg(self.highlight_wires)) {
+            (Ok(wiring), Ok(svg)) => {
+                let div = yew::utils::document().create_element("div").unwrap();
+                div.set_inner_html(&svg);
+                html! {
+                    <>
+                        <p>{format!("Wiring: {}", wiring)}</p>
+                        <div class="segments">{VNode::VRef(div.into())}</div>
+                    </>
+                }
+            }
+            (Err(e), _) | (_, Err(e)) => html! { <p>{e.to_string()}</p> },
+        }
+    }
+}
+
+impl Component for Segments {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            display: advent2021_lib::day08::DAY
+                .example
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned(),
+            highlight_wires: false,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Display(display) => self.display = display,
+            Msg::Highlight => self.highlight_wires = !self.highlight_wires,
+        }
+        true
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        // This component has no properties so we will always return "false".
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <section class="day-odd">
+                <div class="row">
+                    <div class="row-item day-key"><h4>{"Day 8:"}</h4></div>
+                    <div class="row-item day-title"><h2><em>{"Seven Segment Display"}</em></h2></div>
+                </div>
+                <div class="row">
+                    <div class="row-item day-title">
+                        <input type="text" class="display-input" value=self.display.clone() oninput=self.link.callback(|e: InputData| Msg::Display(e.value)) />
+                    </div>
+                    <div class="row-item day-collapse">
+                        <h5 class="button" onclick=self.link.callback(|_| Msg::Highlight)>
+                            { if self.highlight_wires { "☑ Wires" } else { "☐ Wires" } }
+                        </h5>
+                    </div>
+                </div>
+                { self.view_display() }
+            </section>
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use yew::prelude::*;
+
+use advent2021_lib::day18::{ReductionStep, SnailfishNumber};
+
+pub enum Msg {
+    Left(String),
+    Right(String),
+    Add,
+    Previous,
+    Next,
+}
+
+/// Panel to add two snailfish numbers and step through the reduction
+pub struct Snailfish {
+    // `ComponentLink` is like a reference to a component.
+    // It can be used to send messages to the component
+    link: ComponentLink<Self>,
+    left: String,
+    right: String,
+    combined: Option<SnailfishNumber>,
+    steps: Vec<ReductionStep>,
+    /// Number of reduction steps shown
+    current: usize,
+    error: Option<String>,
+}
+
+impl Snailfish {
+    fn add(&mut self) {
+        let left = SnailfishNumber::parse_reduced(&self.left);
+        let right = SnailfishNumber::parse_reduced(&self.right);
+        match (left, right) {
+            (Ok(left), Ok(right)) => {
+                let (combined, steps) = left.add_steps(right);
+                self.combined = Some(combined);
+                self.steps = steps.collect();
+                log::info!("{} reduction steps", self.steps.len());
+                self.current = 0;
+                self.error = None;
+            }
+            _ => {
+                log::error!("parsing error...");
+                self.combined = None;
+                self.steps = Vec::new();
+                self.error = Some("Parsing error, please try again...".to_owned());
+            }
+        }
+    }
+
+    fn view_steps(&self, combined: &SnailfishNumber) -> Html {
+        let number = match self.current {
+            0 => combined,
+            current => &self.steps[current - 1].after,
+        };
+        html! {
+            <>
+                <pre>
+                    {format!("  {}\n+ {}\n= {}", self.left, self.right, combined)}
+                </pre>
+                <div class="row">
+                    <div class="row-item">
+                        <button type="button" disabled={self.current == 0} onclick=self.link.callback(|_| Msg::Previous)>{ "◀ Previous" }</button>
+                        <button type="button" disabled={self.current == self.steps.len()} onclick=self.link.callback(|_| Msg::Next)>{ "Next ▶" }</button>
+                    </div>
+                    <div class="row-item">
+                        <h5>{format!("Step {} / {}", self.current, self.steps.len())}</h5>
+                    </div>
+                </div>
+                {
+                    for self.steps[..self.current].iter().map(|step| {
+                        html! {
+                            <p>{step.reduction.to_string()}{": "}{step.after.to_string()}</p>
+                        }
+                    })
+                }
+                <pre>{number.to_string()}</pre>
+                {
+                    if self.current == self.steps.len() {
+                        html! {
+                            <p>{format!("Reduced, with magnitude {}", number.magnitude())}</p>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </>
+        }
+    }
+}
+
+impl Component for Snailfish {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            left: "[[[[4,3],4],4],[7,[[8,4],9]]]".to_owned(),
+            right: "[1,1]".to_owned(),
+            combined: None,
+            steps: Vec::new(),
+            current: 0,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Left(left) => {
+                self.left = left;
+                false
+            }
+            Msg::Right(right) => {
+                self.right = right;
+                false
+            }
+            Msg::Add => {
+                self.add();
+                true
+            }
+            Msg::Previous => {
+                self.current = self.current.saturating_sub(1);
+                true
+            }
+            Msg::Next => {
+                self.current = (self.current + 1).min(self.steps.len());
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        // This component has no properties so we will always return "false".
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <section class="day-even">
+                <div class="row">
+                    <div class="row-item day-key"><h4>{"Day 18:"}</h4></div>
+                    <div class="row-item day-title"><h2><em>{"Snailfish Reduction Explorer"}</em></h2></div>
+                </div>
+                <div class="row">
+                    <div class="row-item">
+                        <input type="text" value=self.left.clone() oninput=self.link.callback(|e: InputData| Msg::Left(e.value)) />
+                        {" + "}
+                        <input type="text" value=self.right.clone() oninput=self.link.callback(|e: InputData| Msg::Right(e.value)) />
+                    </div>
+                    <div class="row-item day-run">
+                        <button type="button" onclick=self.link.callback(|_| Msg::Add)>{ "▶ Add..." }</button>
+                    </div>
+                </div>
+                {
+                    match (&self.combined, &self.error) {
+                        (_, Some(error)) => html! { <p>{error}</p> },
+                        (Some(combined), None) => self.view_steps(combined),
+                        (None, None) => html! {},
+                    }
+                }
+            </section>
+        }
+    }
+}